@@ -0,0 +1,59 @@
+use arch_program::program_error::ProgramError;
+
+/// Errors returned by the counter program as `ProgramError::Custom(code)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum CounterError {
+    /// Failure explicitly requested through `FaultAction::ReturnError(1)`
+    RequestedFailure = 1,
+    /// The updated counter could not be serialized
+    SerializationFailed = 502,
+    /// The counter account data could not be borrowed for writing
+    AccountBorrowFailed = 503,
+    /// An anchoring fees transaction could not be deserialized, has no input, spends an input
+    /// already spent by another one or there are too many of them
+    InvalidAnchoringTransaction = 504,
+    /// An anchoring output is invalid or there are too many of them
+    InvalidAnchoringOutput = 505,
+    /// A counter using `OverflowPolicy::Error` would go past `u64::MAX`
+    Overflow = 506,
+    /// A counter using `OverflowPolicy::Error` would go below zero
+    Underflow = 507,
+    /// The authority account is not the one controlling the counter
+    InvalidAuthority = 508,
+    /// The counter still uses the v1 layout and must be migrated with `MigrateCounter` first
+    NeedsMigration = 509,
+    /// `MigrateCounter` was called on a counter already using the current layout
+    AlreadyMigrated = 510,
+    /// An account following the first counter of `BatchIncrease` is not writable or not owned by
+    /// the program
+    InvalidBatchAccount = 511,
+    /// `CloseCounter` was asked to send the counter lamports back to the counter itself
+    InvalidCloseDestination = 512,
+}
+
+impl CounterError {
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(CounterError::RequestedFailure),
+            502 => Some(CounterError::SerializationFailed),
+            503 => Some(CounterError::AccountBorrowFailed),
+            504 => Some(CounterError::InvalidAnchoringTransaction),
+            505 => Some(CounterError::InvalidAnchoringOutput),
+            506 => Some(CounterError::Overflow),
+            507 => Some(CounterError::Underflow),
+            508 => Some(CounterError::InvalidAuthority),
+            509 => Some(CounterError::NeedsMigration),
+            510 => Some(CounterError::AlreadyMigrated),
+            511 => Some(CounterError::InvalidBatchAccount),
+            512 => Some(CounterError::InvalidCloseDestination),
+            _ => None,
+        }
+    }
+}
+
+impl From<CounterError> for ProgramError {
+    fn from(error: CounterError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
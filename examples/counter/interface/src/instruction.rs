@@ -0,0 +1,531 @@
+use arch_program::{
+    account::AccountMeta, instruction::Instruction, pubkey::Pubkey, utxo::UtxoMeta,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{error::CounterError, find_counter_address, state::OverflowPolicy};
+
+/// Counter instructions, their Borsh tag being their position: new variants go at the end so that
+/// clients of the deployed program keep sending the same tags
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum CounterInstruction {
+    InitializeCounter(u64, u64, OverflowPolicy),
+    IncreaseCounter,
+    DecreaseCounter,
+    ResetCounter(u64),
+    SetStep(u64),
+    TransferAuthority(Pubkey),
+    /// Upgrades a counter stored with the v1 layout to the current one. Both the counter account
+    /// and the authority must sign, the authority becoming the one controlling the counter
+    MigrateCounter,
+    /// Sends the counter lamports to the third account and returns it to the system program
+    CloseCounter,
+    /// Creates the counter at the address derived from the authority and `name`, funded by the
+    /// authority and anchored to `utxo`. Accounts: counter, authority, system program
+    InitializeNamedCounter {
+        name: String,
+        initial_value: u64,
+        step: u64,
+        overflow_policy: OverflowPolicy,
+        utxo: UtxoMeta,
+    },
+    /// Increases the counter by the given amount instead of its step
+    IncreaseBy(u64),
+    /// Increases by its step the first account and every following account, which have to be
+    /// writable counters owned by the program, all controlled by the authority, anchoring them
+    /// together
+    BatchIncrease,
+    /// Starts recording the counter value after each mutation in a history of the given capacity,
+    /// dropping any previous history
+    EnableHistory(u8),
+}
+
+/// Point of `process_instruction` at which a requested fault is triggered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum FaultStage {
+    /// After the input is decoded, before the counter account is written
+    BeforeStateWrite,
+    /// Right after the counter account is written
+    AfterStateWrite,
+    /// After the anchoring fees transactions are deserialized, only reached when anchoring
+    AfterAnchoringDeserialization,
+    /// At the very end, after `set_transaction_to_sign` when anchoring
+    AfterSetTransactionToSign,
+}
+
+/// How a requested fault makes the instruction fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum FaultAction {
+    /// Return `ProgramError::Custom` with the given code
+    ReturnError(u32),
+    Panic,
+    /// Spin until the compute budget is exhausted
+    ExhaustCompute,
+}
+
+/// Failure to inject while processing the instruction, used by the rollback tests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum FaultPlan {
+    None,
+    Fail {
+        stage: FaultStage,
+        action: FaultAction,
+    },
+}
+
+impl FaultPlan {
+    /// Maps the legacy `should_return_err` / `should_panic` flags to a fault at the end of the
+    /// instruction, the panic taking precedence like it used to
+    pub fn from_flags(should_return_err: bool, should_panic: bool) -> Self {
+        let action = if should_panic {
+            FaultAction::Panic
+        } else if should_return_err {
+            FaultAction::ReturnError(CounterError::RequestedFailure as u32)
+        } else {
+            return FaultPlan::None;
+        };
+
+        FaultPlan::Fail {
+            stage: FaultStage::AfterSetTransactionToSign,
+            action,
+        }
+    }
+}
+
+/// Bitcoin transactions funding the anchoring of the counter state transitions
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Anchoring {
+    /// Serialized fee transactions, every input of each one is added to the anchoring transaction
+    pub fee_txs: Vec<Vec<u8>>,
+    /// Leaves the fee inputs and the outputs out so the anchoring transaction can't be broadcast
+    pub should_fail: bool,
+}
+
+impl Anchoring {
+    pub fn new(fee_txs: Vec<Vec<u8>>) -> Self {
+        Anchoring {
+            fee_txs,
+            should_fail: false,
+        }
+    }
+}
+
+/// Single fee transaction form `(utxo, fee_tx, should_fail)` used by most tests, the utxo only
+/// tells which account the fee transaction was prepared for
+impl From<(UtxoMeta, Vec<u8>, bool)> for Anchoring {
+    fn from((_utxo, fee_tx, should_fail): (UtxoMeta, Vec<u8>, bool)) -> Self {
+        Anchoring {
+            fee_txs: vec![fee_tx],
+            should_fail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CounterInput {
+    pub instruction: CounterInstruction,
+    pub anchoring: Option<Anchoring>,
+    pub fault: FaultPlan,
+    /// Outputs `(script_pubkey, amount in sats)` paid by the anchoring transaction
+    pub outputs: Vec<(Vec<u8>, u64)>,
+}
+
+/// Builds a counter instruction with the counter and its authority as signing accounts
+pub fn counter_instruction(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    instruction: CounterInstruction,
+    fault: FaultPlan,
+    anchoring: Option<Anchoring>,
+    outputs: Vec<(Vec<u8>, u64)>,
+) -> Instruction {
+    let serialized_counter_input = borsh::to_vec(&CounterInput {
+        instruction,
+        anchoring,
+        fault,
+        outputs,
+    })
+    .unwrap();
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta {
+                pubkey: *counter,
+                is_signer: true,
+                is_writable: true,
+            },
+            AccountMeta::new(*authority, true),
+        ],
+        data: serialized_counter_input,
+    }
+}
+
+/// Program derived counters have no private key, so their account can't sign the instruction
+pub fn without_counter_signature(mut instruction: Instruction) -> Instruction {
+    instruction.accounts[0].is_signer = false;
+
+    instruction
+}
+
+pub fn initialize(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    initial_value: u64,
+    step: u64,
+    overflow_policy: OverflowPolicy,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::InitializeCounter(initial_value, step, overflow_policy),
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+/// Initializes the counter named `name` at its address derived from `owner`, created from `utxo`
+pub fn initialize_named(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    name: &str,
+    initial_value: u64,
+    step: u64,
+    overflow_policy: OverflowPolicy,
+    utxo: UtxoMeta,
+) -> Instruction {
+    let (counter, _bump_seed) = find_counter_address(owner, name, program_id);
+
+    let serialized_counter_input = borsh::to_vec(&CounterInput {
+        instruction: CounterInstruction::InitializeNamedCounter {
+            name: name.to_string(),
+            initial_value,
+            step,
+            overflow_policy,
+            utxo,
+        },
+        anchoring: None,
+        fault: FaultPlan::None,
+        outputs: vec![],
+    })
+    .unwrap();
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(counter, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(Pubkey::system_program(), false),
+        ],
+        data: serialized_counter_input,
+    }
+}
+
+/// Builds an increase instruction failing as described by `fault`
+pub fn increase(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    fault: FaultPlan,
+    anchoring: Option<Anchoring>,
+    outputs: Vec<(Vec<u8>, u64)>,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::IncreaseCounter,
+        fault,
+        anchoring,
+        outputs,
+    )
+}
+
+pub fn increase_by(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::IncreaseBy(amount),
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+/// Builds a batch increase of all `counters`, anchored together when `anchoring` is set
+pub fn batch_increase(
+    program_id: &Pubkey,
+    counters: &[Pubkey],
+    authority: &Pubkey,
+    anchoring: Option<Anchoring>,
+    outputs: Vec<(Vec<u8>, u64)>,
+) -> Instruction {
+    let mut instruction = counter_instruction(
+        program_id,
+        &counters[0],
+        authority,
+        CounterInstruction::BatchIncrease,
+        FaultPlan::None,
+        anchoring,
+        outputs,
+    );
+
+    instruction.accounts.extend(
+        counters[1..]
+            .iter()
+            .map(|counter| AccountMeta::new(*counter, true)),
+    );
+
+    instruction
+}
+
+/// Builds a decrease instruction failing as described by `fault`
+pub fn decrease(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    fault: FaultPlan,
+    anchoring: Option<Anchoring>,
+    outputs: Vec<(Vec<u8>, u64)>,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::DecreaseCounter,
+        fault,
+        anchoring,
+        outputs,
+    )
+}
+
+pub fn reset(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey, value: u64) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::ResetCounter(value),
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+pub fn set_step(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    step: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::SetStep(step),
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+pub fn transfer_authority(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::TransferAuthority(*new_authority),
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+pub fn enable_history(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    capacity: u8,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::EnableHistory(capacity),
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+pub fn migrate(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::MigrateCounter,
+        FaultPlan::None,
+        None,
+        vec![],
+    )
+}
+
+pub fn close(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let mut instruction = counter_instruction(
+        program_id,
+        counter,
+        authority,
+        CounterInstruction::CloseCounter,
+        FaultPlan::None,
+        None,
+        vec![],
+    );
+
+    instruction
+        .accounts
+        .push(AccountMeta::new(*destination, false));
+
+    instruction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(counter_input: &CounterInput, expected: &[u8]) {
+        let serialized = borsh::to_vec(counter_input).unwrap();
+
+        assert_eq!(serialized, expected);
+        assert_eq!(
+            &borsh::from_slice::<CounterInput>(&serialized).unwrap(),
+            counter_input
+        );
+    }
+
+    #[test]
+    fn increase_input_wire_format() {
+        round_trip(
+            &CounterInput {
+                instruction: CounterInstruction::IncreaseCounter,
+                anchoring: None,
+                fault: FaultPlan::None,
+                outputs: vec![],
+            },
+            &[1, 0, 0, 0, 0, 0, 0],
+        );
+    }
+
+    #[test]
+    fn initialize_input_wire_format() {
+        round_trip(
+            &CounterInput {
+                instruction: CounterInstruction::InitializeCounter(1, 2, OverflowPolicy::Wrap),
+                anchoring: None,
+                fault: FaultPlan::Fail {
+                    stage: FaultStage::AfterStateWrite,
+                    action: FaultAction::ReturnError(42),
+                },
+                outputs: vec![(vec![0x6a], 0)],
+            },
+            &[
+                &[0][..],
+                &1u64.to_le_bytes(),
+                &2u64.to_le_bytes(),
+                &[1],
+                &[0],
+                &[1, 1, 0],
+                &42u32.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                &[0x6a],
+                &0u64.to_le_bytes(),
+            ]
+            .concat(),
+        );
+    }
+
+    #[test]
+    fn anchored_input_wire_format() {
+        round_trip(
+            &CounterInput {
+                instruction: CounterInstruction::DecreaseCounter,
+                anchoring: Some(Anchoring::new(vec![vec![0xaa], vec![0xbb, 0xcc]])),
+                fault: FaultPlan::None,
+                outputs: vec![],
+            },
+            &[
+                &[2, 1][..],
+                &2u32.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                &[0xaa],
+                &2u32.to_le_bytes(),
+                &[0xbb, 0xcc],
+                &[0, 0],
+                &0u32.to_le_bytes(),
+            ]
+            .concat(),
+        );
+    }
+
+    #[test]
+    fn instruction_variant_tags() {
+        let tag = |instruction: CounterInstruction| borsh::to_vec(&instruction).unwrap()[0];
+
+        assert_eq!(
+            tag(CounterInstruction::InitializeCounter(
+                0,
+                1,
+                OverflowPolicy::Wrap
+            )),
+            0
+        );
+        assert_eq!(tag(CounterInstruction::IncreaseCounter), 1);
+        assert_eq!(tag(CounterInstruction::DecreaseCounter), 2);
+        assert_eq!(tag(CounterInstruction::ResetCounter(0)), 3);
+        assert_eq!(tag(CounterInstruction::SetStep(0)), 4);
+        assert_eq!(
+            borsh::to_vec(&CounterInstruction::TransferAuthority(Pubkey::from_slice(
+                &[9; 32]
+            )))
+            .unwrap(),
+            [&[5][..], &[9; 32]].concat()
+        );
+        assert_eq!(tag(CounterInstruction::MigrateCounter), 6);
+        assert_eq!(tag(CounterInstruction::CloseCounter), 7);
+        assert_eq!(
+            tag(CounterInstruction::InitializeNamedCounter {
+                name: "named".to_string(),
+                initial_value: 0,
+                step: 1,
+                overflow_policy: OverflowPolicy::Wrap,
+                utxo: UtxoMeta::from([0; 32], 0),
+            }),
+            8
+        );
+        assert_eq!(tag(CounterInstruction::IncreaseBy(5)), 9);
+        assert_eq!(tag(CounterInstruction::BatchIncrease), 10);
+        assert_eq!(
+            borsh::to_vec(&CounterInstruction::EnableHistory(16)).unwrap(),
+            vec![11, 16]
+        );
+    }
+}
@@ -0,0 +1,22 @@
+//! Types shared by the counter program and its clients: the instruction wire format, the
+//! counter account layout, the program errors and the instruction builders.
+use arch_program::pubkey::Pubkey;
+
+pub mod error;
+pub mod instruction;
+pub mod state;
+
+/// First seed of program derived counter addresses
+pub const COUNTER_SEED: &[u8] = b"counter";
+/// Maximum length in bytes of a counter name, the limit of a single seed
+pub const MAX_COUNTER_NAME_LEN: usize = 32;
+
+/// Maximum number of caller provided outputs in an anchoring transaction
+pub const MAX_ANCHORING_OUTPUTS: usize = 8;
+/// Maximum number of fee transactions funding an anchoring transaction
+pub const MAX_ANCHORING_FEE_TXS: usize = 4;
+
+/// Finds the address of the counter named `name` owned by `owner`
+pub fn find_counter_address(owner: &Pubkey, name: &str, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUNTER_SEED, owner.as_ref(), name.as_bytes()], program_id)
+}
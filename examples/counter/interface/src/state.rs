@@ -0,0 +1,319 @@
+use arch_program::{program_error::ProgramError, pubkey::Pubkey};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::error::CounterError;
+
+/// Version tag stored as the first byte of counter accounts using the current layout
+pub const COUNTER_DATA_VERSION: u8 = 2;
+/// Version reported by `decode_counter_account` for counters still using the untagged v1 layout
+pub const COUNTER_DATA_LEGACY_VERSION: u8 = 1;
+
+/// Maximum number of entries kept by a counter history
+pub const MAX_HISTORY_CAPACITY: u8 = 32;
+
+/// What happens when moving the counter by one step would go past `u64::MAX` or below zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum OverflowPolicy {
+    /// Fail the instruction with `CounterError::Overflow` or `CounterError::Underflow`
+    Error,
+    /// Wrap around to the other end of the `u64` range
+    Wrap,
+    /// Stay at `u64::MAX` or zero
+    Saturate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CounterData {
+    /// `COUNTER_DATA_VERSION` on chain, lets the layout evolve
+    pub version: u8,
+    pub current_value: u64,
+    pub current_step: u64,
+    pub overflow_policy: OverflowPolicy,
+    /// The only account allowed to mutate the counter
+    pub authority: Pubkey,
+    /// Values recorded after each mutation, once enabled with `EnableHistory`
+    pub history: Option<CounterHistory>,
+}
+
+impl CounterData {
+    pub fn new(
+        current_value: u64,
+        current_step: u64,
+        overflow_policy: OverflowPolicy,
+        authority: Pubkey,
+    ) -> Self {
+        CounterData {
+            version: COUNTER_DATA_VERSION,
+            current_value,
+            current_step,
+            overflow_policy,
+            authority,
+            history: None,
+        }
+    }
+
+    /// Decodes a counter using the current layout, failing with `CounterError::NeedsMigration`
+    /// on v1 accounts
+    pub fn decode(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() == CounterDataV1::LEN {
+            return Err(CounterError::NeedsMigration.into());
+        }
+
+        let counter_data: CounterData =
+            borsh::from_slice(data).map_err(|_e| ProgramError::InvalidAccountData)?;
+
+        if counter_data.version != COUNTER_DATA_VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(counter_data)
+    }
+
+    pub fn value_and_step(&self) -> (u64, u64) {
+        (self.current_value, self.current_step)
+    }
+
+    /// Returns the value after one step, or `None` if it overflows under `OverflowPolicy::Error`
+    pub fn increased_value(&self) -> Option<u64> {
+        self.increased_by(self.current_step)
+    }
+
+    /// Returns the value increased by `amount`, or `None` if it overflows under
+    /// `OverflowPolicy::Error`
+    pub fn increased_by(&self, amount: u64) -> Option<u64> {
+        match self.overflow_policy {
+            OverflowPolicy::Error => self.current_value.checked_add(amount),
+            OverflowPolicy::Wrap => Some(self.current_value.wrapping_add(amount)),
+            OverflowPolicy::Saturate => Some(self.current_value.saturating_add(amount)),
+        }
+    }
+
+    /// Returns the value after one step down, or `None` if it underflows under `OverflowPolicy::Error`
+    pub fn decreased_value(&self) -> Option<u64> {
+        match self.overflow_policy {
+            OverflowPolicy::Error => self.current_value.checked_sub(self.current_step),
+            OverflowPolicy::Wrap => Some(self.current_value.wrapping_sub(self.current_step)),
+            OverflowPolicy::Saturate => Some(self.current_value.saturating_sub(self.current_step)),
+        }
+    }
+
+    /// Returns the entries recorded in the counter history, oldest first, empty when the history
+    /// isn't enabled
+    pub fn history_entries(&self) -> Vec<HistoryEntry> {
+        let Some(history) = &self.history else {
+            return vec![];
+        };
+
+        let len = history.len as usize;
+
+        if len < history.entries.len() {
+            return history.entries[..len].to_vec();
+        }
+
+        let (newest, oldest) = history.entries.split_at(history.next_index as usize);
+
+        oldest.iter().chain(newest).cloned().collect()
+    }
+}
+
+/// Decodes counter account data stored with either the untagged v1 layout or the current one,
+/// v1 counters being widened and reported with `COUNTER_DATA_LEGACY_VERSION`. A v1 counter has no
+/// authority yet, so it is reported with the default pubkey until `MigrateCounter` assigns one
+pub fn decode_counter_account(data: &[u8]) -> Result<CounterData, ProgramError> {
+    if data.len() == CounterDataV1::LEN {
+        let legacy_counter_data: CounterDataV1 =
+            borsh::from_slice(data).map_err(|_e| ProgramError::InvalidAccountData)?;
+
+        return Ok(CounterData {
+            version: COUNTER_DATA_LEGACY_VERSION,
+            ..legacy_counter_data.migrate(Pubkey::default())
+        });
+    }
+
+    CounterData::decode(data)
+}
+
+/// Untagged layout written by the first version of the program, told apart by its length
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CounterDataV1 {
+    pub current_value: u16,
+    pub current_step: u16,
+}
+
+impl CounterDataV1 {
+    /// Serialized size: two `u16`
+    pub const LEN: usize = 2 + 2;
+
+    /// Widens the counter to the current layout. The v1 program panicked on overflow, so migrated
+    /// counters get `OverflowPolicy::Error`, and `authority` becomes the only account allowed to
+    /// mutate them
+    pub fn migrate(self, authority: Pubkey) -> CounterData {
+        CounterData::new(
+            u64::from(self.current_value),
+            u64::from(self.current_step),
+            OverflowPolicy::Error,
+            authority,
+        )
+    }
+}
+
+/// Fixed-size ring buffer of the last values taken by the counter
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CounterHistory {
+    /// Number of entries recorded so far, up to the capacity
+    pub len: u8,
+    /// Slot the next entry is written to, the oldest entry once the buffer is full
+    pub next_index: u8,
+    /// Preallocated to the capacity so the account size doesn't change while recording
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl CounterHistory {
+    pub fn new(capacity: u8) -> Self {
+        CounterHistory {
+            len: 0,
+            next_index: 0,
+            entries: vec![HistoryEntry::default(); capacity as usize],
+        }
+    }
+
+    pub fn record(&mut self, entry: HistoryEntry) {
+        let capacity = self.entries.len();
+
+        self.entries[self.next_index as usize] = entry;
+        self.next_index = ((self.next_index as usize + 1) % capacity) as u8;
+        self.len = self.len.saturating_add(1).min(capacity as u8);
+    }
+}
+
+/// Counter value after a mutation, with the Bitcoin block height and Arch slot it happened at
+#[derive(Debug, Clone, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct HistoryEntry {
+    pub value: u64,
+    pub bitcoin_block_height: u64,
+    pub slot: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: u64) -> HistoryEntry {
+        HistoryEntry {
+            value,
+            bitcoin_block_height: 100 + value,
+            slot: 1000 + value,
+        }
+    }
+
+    #[test]
+    fn counter_data_wire_format() {
+        let counter_data =
+            CounterData::new(7, 3, OverflowPolicy::Saturate, Pubkey::from_slice(&[9; 32]));
+
+        let expected = [
+            &[COUNTER_DATA_VERSION][..],
+            &7u64.to_le_bytes(),
+            &3u64.to_le_bytes(),
+            &[2],
+            &[9; 32],
+            &[0],
+        ]
+        .concat();
+
+        assert_eq!(borsh::to_vec(&counter_data).unwrap(), expected);
+        assert_eq!(CounterData::decode(&expected).unwrap(), counter_data);
+    }
+
+    #[test]
+    fn counter_history_wire_format() {
+        let mut counter_data =
+            CounterData::new(8, 1, OverflowPolicy::Error, Pubkey::from_slice(&[9; 32]));
+        let mut history = CounterHistory::new(2);
+        history.record(entry(8));
+        counter_data.history = Some(history);
+
+        let expected = [
+            &[COUNTER_DATA_VERSION][..],
+            &8u64.to_le_bytes(),
+            &1u64.to_le_bytes(),
+            &[0],
+            &[9; 32],
+            &[1, 1, 1],
+            &2u32.to_le_bytes(),
+            &8u64.to_le_bytes(),
+            &108u64.to_le_bytes(),
+            &1008u64.to_le_bytes(),
+            &[0; 24],
+        ]
+        .concat();
+
+        assert_eq!(borsh::to_vec(&counter_data).unwrap(), expected);
+        assert_eq!(CounterData::decode(&expected).unwrap(), counter_data);
+    }
+
+    #[test]
+    fn decode_counter_account_both_versions() {
+        let authority = Pubkey::from_slice(&[9; 32]);
+
+        let legacy_data = [7, 1, 3, 0];
+
+        assert_eq!(legacy_data.len(), CounterDataV1::LEN);
+        assert_eq!(
+            CounterData::decode(&legacy_data),
+            Err(CounterError::NeedsMigration.into())
+        );
+        assert_eq!(
+            decode_counter_account(&legacy_data).unwrap(),
+            CounterData {
+                version: COUNTER_DATA_LEGACY_VERSION,
+                ..CounterData::new(263, 3, OverflowPolicy::Error, Pubkey::default())
+            }
+        );
+
+        let migrated: CounterData = borsh::from_slice::<CounterDataV1>(&legacy_data)
+            .unwrap()
+            .migrate(authority);
+
+        assert_eq!(
+            migrated,
+            CounterData::new(263, 3, OverflowPolicy::Error, authority)
+        );
+        assert_eq!(
+            CounterData::decode(&borsh::to_vec(&migrated).unwrap()).unwrap(),
+            migrated
+        );
+
+        let counter_data = CounterData::new(u64::MAX, 3, OverflowPolicy::Saturate, authority);
+        let mut data = borsh::to_vec(&counter_data).unwrap();
+
+        assert_eq!(decode_counter_account(&data).unwrap(), counter_data);
+
+        data[0] = 3;
+        assert!(decode_counter_account(&data).is_err());
+    }
+
+    #[test]
+    fn history_entries_oldest_first() {
+        let mut counter_data =
+            CounterData::new(5, 1, OverflowPolicy::Error, Pubkey::from_slice(&[9; 32]));
+
+        assert!(counter_data.history_entries().is_empty());
+
+        let mut history = CounterHistory::new(3);
+        history.record(entry(1));
+        history.record(entry(2));
+        counter_data.history = Some(history.clone());
+
+        assert_eq!(counter_data.history_entries(), vec![entry(1), entry(2)]);
+
+        history.record(entry(3));
+        history.record(entry(4));
+        counter_data.history = Some(history);
+
+        assert_eq!(
+            counter_data.history_entries(),
+            vec![entry(2), entry(3), entry(4)]
+        );
+    }
+}
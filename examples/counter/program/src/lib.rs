@@ -1,30 +1,39 @@
 use arch_program::{
     account::AccountInfo,
     bitcoin::{
-        self, absolute::LockTime, transaction::Version, Address, Amount, Transaction,
-        TxOut,
+        self, absolute::LockTime, transaction::Version, Amount, ScriptBuf, Transaction, TxIn, TxOut,
     },
     entrypoint,
     helper::add_state_transition,
     input_to_sign::InputToSign,
-    msg,
-    program::{next_account_info, set_transaction_to_sign},
     log::sol_log_compute_units,
+    msg,
+    program::{
+        get_bitcoin_block_height, get_clock, invoke_signed, next_account_info,
+        set_transaction_to_sign,
+    },
     program_error::ProgramError,
     pubkey::Pubkey,
-    utxo::UtxoMeta,
+    rent::minimum_rent,
+    system_instruction::create_account_with_anchor,
+};
+use counter_interface::{
+    error::CounterError,
+    find_counter_address,
+    instruction::{CounterInput, CounterInstruction, FaultAction, FaultPlan, FaultStage},
+    state::{CounterData, CounterDataV1, CounterHistory, HistoryEntry, MAX_HISTORY_CAPACITY},
+    COUNTER_SEED, MAX_ANCHORING_FEE_TXS, MAX_ANCHORING_OUTPUTS, MAX_COUNTER_NAME_LEN,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
-use std::str::FromStr;
 
 entrypoint!(process_instruction);
 pub fn process_instruction<'a>(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &'a [AccountInfo<'a>],
     instruction_data: &[u8],
 ) -> Result<(), ProgramError> {
     let account_iter = &mut accounts.iter();
     let account = next_account_info(account_iter)?;
+    let authority = next_account_info(account_iter)?;
 
     let data_len = account
         .data
@@ -39,68 +48,227 @@ pub fn process_instruction<'a>(
 
     let instruction = counter_input.instruction.clone();
 
+    let anchoring_outputs = validate_anchoring_outputs(&counter_input.outputs)?;
+
     sol_log_compute_units();
 
+    trigger_fault(&counter_input.fault, FaultStage::BeforeStateWrite)?;
+
+    // Counters whose state transition is anchored, the first account unless batching
+    let mut anchored_accounts = vec![account];
+
     match instruction {
-        CounterInstruction::InitializeCounter(initial_value, step) => {
+        CounterInstruction::InitializeCounter(initial_value, step, overflow_policy) => {
             if data_len > 0 {
                 return Err(ProgramError::AccountAlreadyInitialized);
             }
 
-            let new_counter_data = CounterData::new(initial_value, step);
+            if !authority.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
 
-            let serialized_counter_data = borsh::to_vec(&new_counter_data)
-                .map_err(|e| ProgramError::BorshIoError(e.to_string()))?;
+            let new_counter_data =
+                CounterData::new(initial_value, step, overflow_policy, *authority.key);
 
-            if serialized_counter_data.len() > data_len {
-                account.realloc(serialized_counter_data.len(), true)?;
+            write_counter_data(account, &new_counter_data, data_len)?;
+        }
+        CounterInstruction::InitializeNamedCounter {
+            name,
+            initial_value,
+            step,
+            overflow_policy,
+            utxo,
+        } => {
+            if data_len > 0 {
+                return Err(ProgramError::AccountAlreadyInitialized);
             }
 
-            account
-                .data
-                .try_borrow_mut()
-                .map_err(|_e| ProgramError::AccountBorrowFailed)?
-                .copy_from_slice(&serialized_counter_data);
+            if !authority.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            if name.len() > MAX_COUNTER_NAME_LEN {
+                return Err(ProgramError::MaxSeedLengthExceeded);
+            }
+
+            let system_program = next_account_info(account_iter)?;
+
+            let (counter_pda, bump_seed) = find_counter_address(authority.key, &name, program_id);
+
+            if counter_pda != *account.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+
+            let new_counter_data =
+                CounterData::new(initial_value, step, overflow_policy, *authority.key);
+
+            let space = borsh::to_vec(&new_counter_data)
+                .map_err(|_e| CounterError::SerializationFailed)?
+                .len();
+
+            invoke_signed(
+                &create_account_with_anchor(
+                    authority.key,
+                    account.key,
+                    minimum_rent(space),
+                    space as u64,
+                    program_id,
+                    utxo.txid()
+                        .try_into()
+                        .map_err(|_e| ProgramError::InvalidInstructionData)?,
+                    utxo.vout(),
+                ),
+                &[account.clone(), authority.clone(), system_program.clone()],
+                &[&[
+                    COUNTER_SEED,
+                    authority.key.as_ref(),
+                    name.as_bytes(),
+                    &[bump_seed],
+                ]],
+            )?;
+
+            write_counter_data(account, &new_counter_data, space)?;
         }
         CounterInstruction::IncreaseCounter => {
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.current_value = counter_data
+                    .increased_value()
+                    .ok_or(CounterError::Overflow)?;
+                Ok(())
+            })?;
+        }
+        CounterInstruction::IncreaseBy(amount) => {
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.current_value = counter_data
+                    .increased_by(amount)
+                    .ok_or(CounterError::Overflow)?;
+                Ok(())
+            })?;
+        }
+        CounterInstruction::BatchIncrease => {
+            for counter_account in account_iter.as_slice() {
+                if !counter_account.is_writable || counter_account.owner != program_id {
+                    msg!(
+                        "batch account {:?} is not a writable counter",
+                        counter_account.key
+                    );
+                    return Err(CounterError::InvalidBatchAccount.into());
+                }
+            }
+
+            anchored_accounts.clear();
+
+            for counter_account in std::iter::once(account).chain(account_iter.by_ref()) {
+                update_counter_data(
+                    counter_account,
+                    authority,
+                    counter_account.data_len(),
+                    |counter_data| {
+                        counter_data.current_value = counter_data
+                            .increased_value()
+                            .ok_or(CounterError::Overflow)?;
+                        Ok(())
+                    },
+                )?;
+
+                anchored_accounts.push(counter_account);
+            }
+
+            msg!("batch increased {} counters", anchored_accounts.len());
+        }
+        CounterInstruction::DecreaseCounter => {
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.current_value = counter_data
+                    .decreased_value()
+                    .ok_or(CounterError::Underflow)?;
+                Ok(())
+            })?;
+        }
+        CounterInstruction::ResetCounter(value) => {
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.current_value = value;
+                Ok(())
+            })?;
+        }
+        CounterInstruction::SetStep(step) => {
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.current_step = step;
+                Ok(())
+            })?;
+        }
+        CounterInstruction::TransferAuthority(new_authority) => {
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.authority = new_authority;
+                Ok(())
+            })?;
+        }
+        CounterInstruction::EnableHistory(capacity) => {
+            if capacity == 0 || capacity > MAX_HISTORY_CAPACITY {
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            update_counter_data(account, authority, data_len, |counter_data| {
+                counter_data.history = Some(CounterHistory::new(capacity));
+                Ok(())
+            })?;
+        }
+        CounterInstruction::MigrateCounter => {
             if data_len == 0 {
                 return Err(ProgramError::UninitializedAccount);
             }
 
-            let serialized_current_counter_data = account
-                .data
-                .try_borrow()
-                .map_err(|_e| ProgramError::AccountBorrowFailed)?;
+            if data_len != CounterDataV1::LEN {
+                return Err(CounterError::AlreadyMigrated.into());
+            }
 
-            let counter_data: CounterData = borsh::from_slice(&serialized_current_counter_data)
-                .map_err(|_e| ProgramError::InvalidAccountData)?;
+            let legacy_counter_data: CounterDataV1 = borsh::from_slice(
+                &account
+                    .data
+                    .try_borrow()
+                    .map_err(|_e| ProgramError::AccountBorrowFailed)?,
+            )
+            .map_err(|_e| ProgramError::InvalidAccountData)?;
+
+            // v1 counters had no authority, the counter keypair signing every instruction
+            if !account.is_signer || !authority.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
 
-            let new_counter_data = CounterData::new(
-                counter_data.current_value + counter_data.current_step,
-                counter_data.current_step,
-            );
+            write_counter_data(
+                account,
+                &legacy_counter_data.migrate(*authority.key),
+                data_len,
+            )?;
+        }
+        CounterInstruction::CloseCounter => {
+            if data_len == 0 {
+                return Err(ProgramError::UninitializedAccount);
+            }
 
-            let new_data =
-                borsh::to_vec(&new_counter_data).map_err(|_e| ProgramError::Custom(502))?;
+            let destination = next_account_info(account_iter)?;
 
-            if new_data.len() > data_len {
-                account.realloc(new_data.len(), true)?;
-            }
+            let counter_data = CounterData::decode(
+                &account
+                    .data
+                    .try_borrow()
+                    .map_err(|_e| ProgramError::AccountBorrowFailed)?,
+            )?;
+
+            check_authority(&counter_data.authority, authority)?;
 
-            drop(serialized_current_counter_data);
-            account
-                .data
-                .try_borrow_mut()
-                .map_err(|_e| ProgramError::Custom(503))?
-                .copy_from_slice(&new_data);
+            close_counter_account(account, destination)?;
         }
     }
 
-    if counter_input.anchoring.is_some() {
-        let (_utxo, serialized_tx, anchoring_should_fail) = counter_input.anchoring.unwrap();
+    trigger_fault(&counter_input.fault, FaultStage::AfterStateWrite)?;
 
-        let fees_tx: Transaction = bitcoin::consensus::deserialize(&serialized_tx)
-            .map_err(|_e| ProgramError::Custom(504))?;
+    if let Some(anchoring) = counter_input.anchoring {
+        let fee_inputs = collect_fee_inputs(&anchoring.fee_txs)?;
+
+        trigger_fault(
+            &counter_input.fault,
+            FaultStage::AfterAnchoringDeserialization,
+        )?;
 
         let mut tx = Transaction {
             version: Version::TWO,
@@ -109,73 +277,209 @@ pub fn process_instruction<'a>(
             output: vec![],
         };
 
-        add_state_transition(&mut tx, account);
+        for anchored_account in &anchored_accounts {
+            add_state_transition(&mut tx, anchored_account);
+        }
+
+        if !anchoring.should_fail {
+            tx.input.extend(fee_inputs);
+
+            tx.output.extend(anchoring_outputs);
+        }
+        let inputs: Vec<InputToSign> = anchored_accounts
+            .iter()
+            .enumerate()
+            .map(|(index, anchored_account)| InputToSign {
+                index: index as u32,
+                signer: *anchored_account.key,
+            })
+            .collect();
+
+        sol_log_compute_units();
+        set_transaction_to_sign(accounts, &tx, &inputs)?
+    }
+
+    trigger_fault(&counter_input.fault, FaultStage::AfterSetTransactionToSign)?;
+
+    Ok(())
+}
 
-        let index = 0;
+/// Deserializes the anchoring fee transactions and returns all their inputs, rejecting empty
+/// transactions and inputs spent twice
+fn collect_fee_inputs(fee_txs: &[Vec<u8>]) -> Result<Vec<TxIn>, ProgramError> {
+    if fee_txs.is_empty() || fee_txs.len() > MAX_ANCHORING_FEE_TXS {
+        return Err(CounterError::InvalidAnchoringTransaction.into());
+    }
 
-        if !anchoring_should_fail {
-            tx.input.push(fees_tx.input[0].clone());
+    let mut fee_inputs: Vec<TxIn> = vec![];
 
-            let script_buff = Address::from_str("bcrt1q9lu00cj3y0qzm6wqr6nr46s877259uz9r802sm")
-                .map_err(|_e| ProgramError::Custom(505))?
-                .assume_checked()
-                .script_pubkey();
+    for serialized_tx in fee_txs {
+        let fees_tx: Transaction = bitcoin::consensus::deserialize(serialized_tx)
+            .map_err(|_e| CounterError::InvalidAnchoringTransaction)?;
 
-            if counter_input.add_output.is_some() {
-                let amount: u64 = counter_input.add_output.unwrap();
+        if fees_tx.input.is_empty() {
+            return Err(CounterError::InvalidAnchoringTransaction.into());
+        }
 
-                tx.output.push(TxOut {
-                    value: Amount::from_sat(amount),
-                    script_pubkey: script_buff,
-                })
+        for input in fees_tx.input {
+            if fee_inputs
+                .iter()
+                .any(|fee_input| fee_input.previous_output == input.previous_output)
+            {
+                msg!("fee input {} spent twice", input.previous_output);
+                return Err(CounterError::InvalidAnchoringTransaction.into());
             }
+
+            fee_inputs.push(input);
         }
-        let inputs = [InputToSign {
-            index,
-            signer: account.key.clone(),
-        }];
+    }
 
-        sol_log_compute_units();
-        set_transaction_to_sign(accounts, &tx, &inputs)?
+    Ok(fee_inputs)
+}
+
+/// Turns the caller provided `(script_pubkey, amount)` pairs into anchoring transaction outputs,
+/// rejecting empty scripts, dust amounts, valued OP_RETURN outputs and oversized lists
+fn validate_anchoring_outputs(outputs: &[(Vec<u8>, u64)]) -> Result<Vec<TxOut>, ProgramError> {
+    if outputs.len() > MAX_ANCHORING_OUTPUTS {
+        return Err(CounterError::InvalidAnchoringOutput.into());
+    }
+
+    outputs
+        .iter()
+        .map(|(script_pubkey, amount)| {
+            let script_pubkey = ScriptBuf::from_bytes(script_pubkey.clone());
+            let value = Amount::from_sat(*amount);
+
+            let is_valid = if script_pubkey.is_op_return() {
+                value == Amount::ZERO
+            } else {
+                !script_pubkey.is_empty() && value >= script_pubkey.minimal_non_dust()
+            };
+
+            if !is_valid {
+                msg!("invalid anchoring output {:?} {}", script_pubkey, value);
+                return Err(CounterError::InvalidAnchoringOutput.into());
+            }
+
+            Ok(TxOut {
+                value,
+                script_pubkey,
+            })
+        })
+        .collect()
+}
+
+/// Reads the counter stored in `account`, checks that `authority` controls it and signed,
+/// applies `update` to it and writes it back
+fn update_counter_data<F>(
+    account: &AccountInfo,
+    authority: &AccountInfo,
+    data_len: usize,
+    update: F,
+) -> Result<(), ProgramError>
+where
+    F: FnOnce(&mut CounterData) -> Result<(), ProgramError>,
+{
+    if data_len == 0 {
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let mut counter_data = CounterData::decode(
+        &account
+            .data
+            .try_borrow()
+            .map_err(|_e| ProgramError::AccountBorrowFailed)?,
+    )?;
+
+    check_authority(&counter_data.authority, authority)?;
+
+    update(&mut counter_data)?;
+
+    if let Some(history) = counter_data.history.as_mut() {
+        history.record(HistoryEntry {
+            value: counter_data.current_value,
+            bitcoin_block_height: get_bitcoin_block_height(),
+            slot: get_clock().slot,
+        });
     }
 
-    if counter_input.should_panic {
-        panic!("PANICKED BY REQUEST");
+    write_counter_data(account, &counter_data, data_len)
+}
+
+/// Fails unless `authority` is the account controlling the counter and signed the transaction
+fn check_authority(
+    counter_authority: &Pubkey,
+    authority: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if *counter_authority != *authority.key {
+        return Err(CounterError::InvalidAuthority.into());
     }
 
-    if counter_input.should_return_err {
-        return Err(ProgramError::Custom(1));
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
     Ok(())
 }
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub enum CounterInstruction {
-    InitializeCounter(u16, u16),
-    IncreaseCounter,
-}
+/// Moves all the lamports of `account` to `destination`, empties its data and hands it back to
+/// the system program. Fails when `destination` is the counter itself, whose lamports would
+/// otherwise be zeroed right after being credited
+fn close_counter_account(
+    account: &AccountInfo,
+    destination: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if account.key == destination.key {
+        return Err(CounterError::InvalidCloseDestination.into());
+    }
+
+    let lamports = account.lamports();
+
+    let destination_lamports = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct CounterInput {
-    pub instruction: CounterInstruction,
-    pub anchoring: Option<(UtxoMeta, Vec<u8>, bool)>,
-    pub should_return_err: bool,
-    pub should_panic: bool,
-    pub add_output: Option<u64>,
+    **destination.try_borrow_mut_lamports()? = destination_lamports;
+    **account.try_borrow_mut_lamports()? = 0;
+
+    account.realloc(0, true)?;
+    account.assign(&Pubkey::system_program());
+
+    Ok(())
 }
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct CounterData {
-    current_value: u16,
-    current_step: u16,
+/// Serializes `counter_data` into `account`, resizing the account if needed
+fn write_counter_data(
+    account: &AccountInfo,
+    counter_data: &CounterData,
+    data_len: usize,
+) -> Result<(), ProgramError> {
+    let new_data = borsh::to_vec(counter_data).map_err(|_e| CounterError::SerializationFailed)?;
+
+    if new_data.len() != data_len {
+        account.realloc(new_data.len(), true)?;
+    }
+
+    account
+        .data
+        .try_borrow_mut()
+        .map_err(|_e| CounterError::AccountBorrowFailed)?
+        .copy_from_slice(&new_data);
+
+    Ok(())
 }
 
-impl CounterData {
-    pub fn new(current_value: u16, current_step: u16) -> Self {
-        CounterData {
-            current_value,
-            current_step,
-        }
+/// Makes the instruction fail as requested by `fault` when reaching `current_stage`
+fn trigger_fault(fault: &FaultPlan, current_stage: FaultStage) -> Result<(), ProgramError> {
+    match fault {
+        FaultPlan::Fail { stage, action } if *stage == current_stage => match action {
+            FaultAction::ReturnError(code) => Err(ProgramError::Custom(*code)),
+            FaultAction::Panic => panic!("PANICKED BY REQUEST"),
+            FaultAction::ExhaustCompute => loop {
+                sol_log_compute_units();
+            },
+        },
+        _ => Ok(()),
     }
 }
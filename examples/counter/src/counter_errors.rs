@@ -0,0 +1,60 @@
+use arch_sdk::Status;
+use counter_interface::error::CounterError;
+
+/// Decodes the counter error out of a failed transaction status, `None` if the transaction
+/// did not fail with a custom program error known to the counter program
+pub fn counter_error_from_status(status: &Status) -> Option<CounterError> {
+    match status {
+        Status::Failed(message) => {
+            parse_custom_error_code(message).and_then(CounterError::from_code)
+        }
+        _ => None,
+    }
+}
+
+/// Extracts the code of a custom program error from a failure message, accepting both the
+/// `custom program error: 0x1f6` display form and the `Custom(502)` debug form
+pub fn parse_custom_error_code(message: &str) -> Option<u32> {
+    const DISPLAY_PREFIX: &str = "custom program error: ";
+    const DEBUG_PREFIX: &str = "Custom(";
+
+    let lowercase_message = message.to_ascii_lowercase();
+
+    if let Some(position) = lowercase_message.find(DISPLAY_PREFIX) {
+        let code = &message[position + DISPLAY_PREFIX.len()..];
+        let code: String = code
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+
+        return match code.strip_prefix("0x") {
+            Some(hex_code) => u32::from_str_radix(hex_code, 16).ok(),
+            None => code.parse().ok(),
+        };
+    }
+
+    let position = message.find(DEBUG_PREFIX)?;
+    let code = &message[position + DEBUG_PREFIX.len()..];
+
+    code[..code.find(')')?].parse().ok()
+}
+
+#[test]
+fn parse_custom_error_code_formats() {
+    assert_eq!(
+        parse_custom_error_code("Error processing Instruction 0: custom program error: 0x1fa"),
+        Some(506)
+    );
+    assert_eq!(
+        parse_custom_error_code("Custom program error: 508"),
+        Some(508)
+    );
+    assert_eq!(parse_custom_error_code("Custom(505)"), Some(505));
+    assert_eq!(parse_custom_error_code("MissingRequiredSignature"), None);
+
+    assert_eq!(
+        counter_error_from_status(&Status::Failed("Custom(507)".to_string())),
+        Some(CounterError::Underflow)
+    );
+    assert_eq!(counter_error_from_status(&Status::Processed), None);
+}
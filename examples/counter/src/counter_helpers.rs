@@ -3,12 +3,10 @@ use arch_program::utxo::UtxoMeta;
 use arch_sdk::blocking::{prepare_fees, ArchRpcClient, BitcoinHelper};
 use arch_sdk::{ArchError, Config};
 use bitcoin::key::{Secp256k1, UntweakedKeypair};
-use bitcoin::{Address, XOnlyPublicKey};
-use borsh::BorshDeserialize;
+use bitcoin::{Address, Network, XOnlyPublicKey};
+use counter_interface::state::{decode_counter_account, CounterData};
 use rand_core::OsRng;
 
-use crate::counter_instructions::CounterData;
-
 pub const DEFAULT_LOG_LEVEL: &str = "info";
 
 pub fn print_title(title: &str, color: u8) {
@@ -78,12 +76,96 @@ pub(crate) fn get_account_counter(account_pubkey: &Pubkey) -> Result<CounterData
 
     let account_info = client.read_account_info(*account_pubkey).unwrap();
 
-    let mut account_info_data = account_info.data.as_slice();
+    decode_counter_account(&account_info.data)
+        .map_err(|e| ArchError::ProgramError(format!("Error corrupted account data {:?}", e)))
+}
+
+/// Builds an anchoring output `(script_pubkey, amount)` paying `amount` sats to a regtest address
+pub(crate) fn address_output(address: &str, amount: u64) -> (Vec<u8>, u64) {
+    let script_pubkey = address
+        .parse::<Address<_>>()
+        .unwrap()
+        .require_network(Network::Regtest)
+        .unwrap()
+        .script_pubkey();
+
+    (script_pubkey.to_bytes(), amount)
+}
 
-    let account_counter = CounterData::deserialize(&mut account_info_data)
-        .map_err(|e| ArchError::ProgramError(format!("Error corrupted account data {}", e)))?;
+/// Compute units used by one top level instruction, read from the transaction logs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ComputeUnitProfile {
+    pub program_id: String,
+    /// Units remaining at each `sol_log_compute_units` call, in call order
+    pub remaining: Vec<u64>,
+    /// Units consumed by the whole instruction, once the runtime reported it
+    pub consumed: Option<u64>,
+    /// Units the instruction was allowed to consume
+    pub budget: Option<u64>,
+}
 
-    Ok(account_counter)
+impl ComputeUnitProfile {
+    /// Units consumed between consecutive `sol_log_compute_units` checkpoints
+    pub fn deltas(&self) -> Vec<u64> {
+        self.remaining
+            .windows(2)
+            .map(|checkpoints| checkpoints[0].saturating_sub(checkpoints[1]))
+            .collect()
+    }
+}
+
+/// Splits the `processed_tx.logs` lines into one compute unit profile per top level instruction,
+/// using the `Program <id> invoke [1]`, `Program consumption: <n> units remaining` and
+/// `Program <id> consumed <n> of <budget> compute units` lines
+pub(crate) fn parse_compute_unit_profiles(logs: &[String]) -> Vec<ComputeUnitProfile> {
+    let mut profiles: Vec<ComputeUnitProfile> = vec![];
+    let mut depth = 0usize;
+
+    for line in logs {
+        let Some(message) = line.trim().strip_prefix("Program ") else {
+            continue;
+        };
+
+        if let Some(remaining) = message
+            .strip_prefix("consumption: ")
+            .and_then(|message| message.strip_suffix(" units remaining"))
+        {
+            if let (Some(profile), Ok(remaining)) = (profiles.last_mut(), remaining.parse()) {
+                profile.remaining.push(remaining);
+            }
+            continue;
+        }
+
+        let mut words = message.split_whitespace();
+        let (Some(program_id), Some(event)) = (words.next(), words.next()) else {
+            continue;
+        };
+
+        match event {
+            "invoke" => {
+                depth += 1;
+
+                if depth == 1 {
+                    profiles.push(ComputeUnitProfile {
+                        program_id: program_id.to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+            "consumed" if depth == 1 => {
+                let counts: Vec<u64> = words.filter_map(|word| word.parse().ok()).collect();
+
+                if let (Some(profile), &[consumed, budget]) = (profiles.last_mut(), &counts[..]) {
+                    profile.consumed = Some(consumed);
+                    profile.budget = Some(budget);
+                }
+            }
+            "success" | "failed:" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    profiles
 }
 
 pub(crate) fn generate_anchoring(account_pubkey: &Pubkey) -> (UtxoMeta, Vec<u8>) {
@@ -100,3 +182,41 @@ pub(crate) fn generate_anchoring(account_pubkey: &Pubkey) -> (UtxoMeta, Vec<u8>)
         hex::decode(fees_psbt).unwrap(),
     )
 }
+
+#[test]
+fn parse_compute_unit_profiles_logs() {
+    let logs: Vec<String> = [
+        "Program counter111 invoke [1]",
+        "Program log: data_len: 52",
+        "Program consumption: 9800 units remaining",
+        "Program system111 invoke [2]",
+        "Program system111 consumed 150 of 9000 compute units",
+        "Program system111 success",
+        "Program consumption: 7300 units remaining",
+        "Program counter111 consumed 3100 of 10000 compute units",
+        "Program counter111 success",
+        "Program counter111 invoke [1]",
+        "Program consumption: 9900 units remaining",
+        "Program counter111 failed: custom program error: 0x1fa",
+    ]
+    .iter()
+    .map(|line| line.to_string())
+    .collect();
+
+    let profiles = parse_compute_unit_profiles(&logs);
+
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(
+        profiles[0],
+        ComputeUnitProfile {
+            program_id: "counter111".to_string(),
+            remaining: vec![9800, 7300],
+            consumed: Some(3100),
+            budget: Some(10000),
+        }
+    );
+    assert_eq!(profiles[0].deltas(), vec![2500]);
+    assert_eq!(profiles[1].remaining, vec![9900]);
+    assert_eq!(profiles[1].consumed, None);
+    assert!(profiles[1].deltas().is_empty());
+}
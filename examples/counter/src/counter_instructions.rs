@@ -1,4 +1,3 @@
-use arch_program::account::AccountMeta;
 use arch_program::instruction::Instruction;
 use arch_program::pubkey::Pubkey;
 use arch_program::rent::minimum_rent;
@@ -10,14 +9,32 @@ use arch_sdk::blocking::{ArchRpcClient, BitcoinHelper};
 use arch_sdk::{build_and_sign_transaction, generate_new_keypair, ArchError, Config};
 use bitcoin::key::Keypair;
 use bitcoin::XOnlyPublicKey;
-use borsh::{BorshDeserialize, BorshSerialize};
+use counter_interface::find_counter_address;
+use counter_interface::instruction::{self, Anchoring, FaultPlan};
+use counter_interface::state::{decode_counter_account, CounterDataV1, OverflowPolicy};
 
 use tracing::{debug, error};
 
 pub(crate) fn start_new_counter(
     program_pubkey: &Pubkey,
-    step: u16,
-    initial_value: u16,
+    step: u64,
+    initial_value: u64,
+    fee_payer_keypair: &Keypair,
+) -> Result<(Pubkey, Keypair), ArchError> {
+    start_new_counter_with_policy(
+        program_pubkey,
+        step,
+        initial_value,
+        OverflowPolicy::Error,
+        fee_payer_keypair,
+    )
+}
+
+pub(crate) fn start_new_counter_with_policy(
+    program_pubkey: &Pubkey,
+    step: u64,
+    initial_value: u64,
+    overflow_policy: OverflowPolicy,
     fee_payer_keypair: &Keypair,
 ) -> Result<(Pubkey, Keypair), ArchError> {
     let config = Config::localnet();
@@ -71,29 +88,16 @@ pub(crate) fn start_new_counter(
 
     println!("\x1b[32m Step 2/3 Successful :\x1b[0m Ownership Successfully assigned to program!");
 
-    let serialized_counter_input = borsh::to_vec(&CounterInput {
-        instruction: CounterInstruction::InitializeCounter(1, 1),
-        anchoring: None,
-        should_return_err: false,
-        should_panic: false,
-        add_output: None,
-    })
-    .unwrap();
-
     let tx = build_and_sign_transaction(
         ArchMessage::new(
-            &[arch_program::instruction::Instruction {
-                program_id: *program_pubkey,
-                accounts: vec![
-                    AccountMeta {
-                        pubkey: account_pubkey,
-                        is_signer: true,
-                        is_writable: true,
-                    },
-                    AccountMeta::new(fee_payer_pubkey, true),
-                ],
-                data: serialized_counter_input,
-            }],
+            &[instruction::initialize(
+                program_pubkey,
+                &account_pubkey,
+                &fee_payer_pubkey,
+                initial_value,
+                step,
+                overflow_policy,
+            )],
             Some(fee_payer_pubkey),
             client.get_best_finalized_block_hash().unwrap(),
         ),
@@ -110,11 +114,12 @@ pub(crate) fn start_new_counter(
 
     let account_info = client.read_account_info(account_pubkey).unwrap();
 
-    let mut account_info_data = account_info.data.as_slice();
-
-    let account_counter = CounterData::deserialize(&mut account_info_data).unwrap();
+    let account_counter = decode_counter_account(&account_info.data).unwrap();
 
-    if account_counter != CounterData::new(initial_value, step) {
+    if account_counter.value_and_step() != (initial_value, step)
+        || account_counter.overflow_policy != overflow_policy
+        || account_counter.authority != fee_payer_pubkey
+    {
         error!("Account content different from provided initial step and initial value !");
 
         debug!("Account info found within account {:?}", account_info);
@@ -131,34 +136,118 @@ pub(crate) fn start_new_counter(
     Ok((account_pubkey, account_key_pair))
 }
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub enum CounterInstruction {
-    InitializeCounter(u16, u16),
-    IncreaseCounter,
-}
+/// Creates a program owned counter account holding the zeroed 4 bytes v1 layout, the state a
+/// counter initialized to (0,0) by the first version of the program was left in
+pub(crate) fn start_legacy_counter(
+    program_pubkey: &Pubkey,
+    fee_payer_keypair: &Keypair,
+) -> Result<(Pubkey, Keypair), ArchError> {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let fee_payer_pubkey = Pubkey::from_slice(
+        &XOnlyPublicKey::from_keypair(fee_payer_keypair)
+            .0
+            .serialize(),
+    );
+
+    let (account_key_pair, account_pubkey, _) = generate_new_keypair(config.network);
+
+    let helper = BitcoinHelper::new(&config).expect("Failed to create BitcoinHelper");
+
+    let (txid, vout) = helper.send_utxo(account_pubkey).unwrap();
+
+    let tx = build_and_sign_transaction(
+        ArchMessage::new(
+            &[system_instruction::create_account_with_anchor(
+                &fee_payer_pubkey,
+                &account_pubkey,
+                minimum_rent(100),
+                CounterDataV1::LEN as u64,
+                program_pubkey,
+                hex::decode(txid).unwrap().try_into().unwrap(),
+                vout,
+            )],
+            Some(fee_payer_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![*fee_payer_keypair, account_key_pair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(tx).unwrap();
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
-pub struct CounterData {
-    pub current_value: u16,
-    pub current_step: u16,
+    let processed_tx = client.wait_for_processed_transaction(&txid).unwrap();
+
+    debug!(
+        "processed_tx for creating legacy account : {:?}",
+        processed_tx
+    );
+
+    Ok((account_pubkey, account_key_pair))
 }
 
-impl CounterData {
-    pub fn new(current_value: u16, current_step: u16) -> Self {
-        CounterData {
-            current_value,
-            current_step,
-        }
+/// Initializes a counter at the address derived from the owner key and `name`, returning that
+/// address. The owner pays for the account and is the counter authority
+pub(crate) fn start_named_counter(
+    program_pubkey: &Pubkey,
+    name: &str,
+    step: u64,
+    initial_value: u64,
+    owner_keypair: &Keypair,
+) -> Result<Pubkey, ArchError> {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let owner_pubkey =
+        Pubkey::from_slice(&XOnlyPublicKey::from_keypair(owner_keypair).0.serialize());
+
+    let (account_pubkey, _bump_seed) = find_counter_address(&owner_pubkey, name, program_pubkey);
+
+    let helper = BitcoinHelper::new(&config).expect("Failed to create BitcoinHelper");
+
+    let (txid, vout) = helper.send_utxo(account_pubkey).unwrap();
+
+    let tx = build_and_sign_transaction(
+        ArchMessage::new(
+            &[instruction::initialize_named(
+                program_pubkey,
+                &owner_pubkey,
+                name,
+                initial_value,
+                step,
+                OverflowPolicy::Error,
+                UtxoMeta::from(hex::decode(txid).unwrap().try_into().unwrap(), vout),
+            )],
+            Some(owner_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![*owner_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(tx).unwrap();
+
+    let processed_tx = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!("processed_tx: {:?}", processed_tx);
+
+    let account_info = client.read_account_info(account_pubkey)?;
+
+    let account_counter = decode_counter_account(&account_info.data)
+        .map_err(|e| ArchError::ProgramError(format!("Error corrupted account data {:?}", e)))?;
+
+    if account_counter.value_and_step() != (initial_value, step)
+        || account_counter.authority != owner_pubkey
+    {
+        return Err(ArchError::ProgramError(
+            "Account content after initialization is wrong !".to_string(),
+        ));
     }
-}
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct CounterInput {
-    pub instruction: CounterInstruction,
-    pub anchoring: Option<(UtxoMeta, Vec<u8>, bool)>,
-    pub should_return_err: bool,
-    pub should_panic: bool,
-    pub add_output: Option<u64>,
+    Ok(account_pubkey)
 }
 
 pub(crate) fn get_counter_increase_instruction(
@@ -168,27 +257,33 @@ pub(crate) fn get_counter_increase_instruction(
     should_return_err: bool,
     should_panic: bool,
     anchoring: Option<(UtxoMeta, Vec<u8>, bool)>,
-    add_output: Option<u64>,
+    outputs: Vec<(Vec<u8>, u64)>,
 ) -> Instruction {
-    let serialized_counter_input = borsh::to_vec(&CounterInput {
-        instruction: CounterInstruction::IncreaseCounter,
-        anchoring,
-        should_return_err,
-        should_panic,
-        add_output,
-    })
-    .unwrap();
-
-    Instruction {
-        program_id: *program_pubkey,
-        accounts: vec![
-            AccountMeta {
-                pubkey: *account_pubkey,
-                is_signer: true,
-                is_writable: true,
-            },
-            AccountMeta::new(*fee_payer_pubkey, true),
-        ],
-        data: serialized_counter_input,
-    }
+    instruction::increase(
+        program_pubkey,
+        account_pubkey,
+        fee_payer_pubkey,
+        FaultPlan::from_flags(should_return_err, should_panic),
+        anchoring.map(Anchoring::from),
+        outputs,
+    )
+}
+
+pub(crate) fn get_counter_decrease_instruction(
+    program_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    fee_payer_pubkey: &Pubkey,
+    should_return_err: bool,
+    should_panic: bool,
+    anchoring: Option<(UtxoMeta, Vec<u8>, bool)>,
+    outputs: Vec<(Vec<u8>, u64)>,
+) -> Instruction {
+    instruction::decrease(
+        program_pubkey,
+        account_pubkey,
+        fee_payer_pubkey,
+        FaultPlan::from_flags(should_return_err, should_panic),
+        anchoring.map(Anchoring::from),
+        outputs,
+    )
 }
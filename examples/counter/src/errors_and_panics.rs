@@ -1,6 +1,7 @@
 use crate::{
+    counter_errors::{counter_error_from_status, parse_custom_error_code},
     counter_helpers::{generate_anchoring, get_account_counter},
-    counter_instructions::{get_counter_increase_instruction, start_new_counter, CounterData},
+    counter_instructions::{get_counter_increase_instruction, start_new_counter},
     ELF_PATH, PROGRAM_FILE_PATH,
 };
 use arch_program::sanitized::ArchMessage;
@@ -9,6 +10,11 @@ use arch_sdk::{
     build_and_sign_transaction, generate_new_keypair, with_secret_key_file, Config, RollbackStatus,
     Status,
 };
+use counter_interface::{
+    error::CounterError,
+    instruction::{self, FaultAction, FaultPlan, FaultStage},
+    state::COUNTER_DATA_VERSION,
+};
 use serial_test::serial;
 
 #[ignore]
@@ -57,7 +63,7 @@ fn counter_inc_single_instruction_fail() {
         true,
         false,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -81,7 +87,12 @@ fn counter_inc_single_instruction_fail() {
         Status::Failed { .. }
     ));
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::RequestedFailure)
+    );
+
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -127,7 +138,7 @@ fn counter_inc_single_instruction_panic() {
         false,
         true,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -151,7 +162,7 @@ fn counter_inc_single_instruction_panic() {
         Status::Failed { .. }
     ));
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -197,7 +208,7 @@ fn counter_inc_two_instructions_1st_fail() {
         true,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -207,7 +218,7 @@ fn counter_inc_two_instructions_1st_fail() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -231,7 +242,12 @@ fn counter_inc_two_instructions_1st_fail() {
         Status::Failed { .. }
     ));
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(
+        counter_error_from_status(&processed_transactions.status),
+        Some(CounterError::RequestedFailure)
+    );
+
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -283,7 +299,7 @@ fn counter_inc_two_instructions_2nd_fail() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -293,7 +309,7 @@ fn counter_inc_two_instructions_2nd_fail() {
         true,
         false,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -316,7 +332,12 @@ fn counter_inc_two_instructions_2nd_fail() {
         Status::Failed { .. }
     ));
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::RequestedFailure)
+    );
+
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -363,7 +384,7 @@ fn counter_inc_two_instructions_1st_panic() {
         false,
         true,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -373,7 +394,7 @@ fn counter_inc_two_instructions_1st_panic() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -396,7 +417,7 @@ fn counter_inc_two_instructions_1st_panic() {
         Status::Failed { .. }
     ));
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -443,7 +464,7 @@ fn counter_inc_two_instructions_2nd_panic() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -453,7 +474,7 @@ fn counter_inc_two_instructions_2nd_panic() {
         false,
         true,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -477,7 +498,7 @@ fn counter_inc_two_instructions_2nd_panic() {
         Status::Failed { .. }
     ));
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -523,7 +544,7 @@ fn counter_inc_two_transactions_1st_fail() {
         true,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -533,7 +554,7 @@ fn counter_inc_two_transactions_1st_fail() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let first_increase_transaction = build_and_sign_transaction(
@@ -578,7 +599,7 @@ fn counter_inc_two_transactions_1st_fail() {
         Status::Processed
     ));
 
-    assert_eq!(final_account_data, CounterData::new(2, 1));
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -624,7 +645,7 @@ fn counter_inc_two_transactions_2nd_fail() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -634,7 +655,7 @@ fn counter_inc_two_transactions_2nd_fail() {
         true,
         false,
         None,
-        None,
+        vec![],
     );
 
     let first_increase_transaction = build_and_sign_transaction(
@@ -678,7 +699,7 @@ fn counter_inc_two_transactions_2nd_fail() {
         Status::Processed
     ));
 
-    assert_eq!(final_account_data, CounterData::new(2, 1));
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -724,7 +745,7 @@ fn counter_inc_two_transactions_1st_panic() {
         false,
         true,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -734,7 +755,7 @@ fn counter_inc_two_transactions_1st_panic() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let first_increase_transaction = build_and_sign_transaction(
@@ -779,7 +800,7 @@ fn counter_inc_two_transactions_1st_panic() {
         Status::Processed
     ));
 
-    assert_eq!(final_account_data, CounterData::new(2, 1));
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -823,7 +844,7 @@ fn counter_inc_two_transactions_2nd_panic() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -833,7 +854,7 @@ fn counter_inc_two_transactions_2nd_panic() {
         false,
         true,
         None,
-        None,
+        vec![],
     );
 
     let first_increase_transaction = build_and_sign_transaction(
@@ -877,7 +898,7 @@ fn counter_inc_two_transactions_2nd_panic() {
         Status::Processed
     ));
 
-    assert_eq!(final_account_data, CounterData::new(2, 1));
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -921,7 +942,7 @@ fn counter_init_and_inc_anchored_fail() {
         false,
         false,
         Some((anchoring.0, anchoring.1, true)),
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -956,7 +977,7 @@ fn counter_init_and_inc_anchored_fail() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -1000,7 +1021,7 @@ fn counter_init_and_inc_anchored_fail_inc_state() {
         false,
         false,
         Some((anchoring.0, anchoring.1, true)),
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -1010,7 +1031,7 @@ fn counter_init_and_inc_anchored_fail_inc_state() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -1046,7 +1067,7 @@ fn counter_init_and_inc_anchored_fail_inc_state() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -1094,7 +1115,7 @@ fn counter_init_and_two_inc_anchored_fail() {
         false,
         false,
         Some((anchoring.0, anchoring.1, true)),
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -1104,7 +1125,7 @@ fn counter_init_and_two_inc_anchored_fail() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -1136,7 +1157,7 @@ fn counter_init_and_two_inc_anchored_fail() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 }
 
 #[ignore]
@@ -1186,7 +1207,7 @@ fn counter_init_and_two_inc_second_anchored_fail() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -1196,7 +1217,7 @@ fn counter_init_and_two_inc_second_anchored_fail() {
         false,
         false,
         Some((anchoring.0, anchoring.1, true)),
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -1231,7 +1252,7 @@ fn counter_init_and_two_inc_second_anchored_fail() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(1, 1));
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
 
     let account_info = client.read_account_info(account_pubkey).unwrap();
 
@@ -1305,7 +1326,7 @@ fn counter_init_and_two_inc_tx_anchored_fail_2nd_succeed() {
         false,
         false,
         Some((first_anchoring.0, first_anchoring.1, true)),
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -1315,7 +1336,7 @@ fn counter_init_and_two_inc_tx_anchored_fail_2nd_succeed() {
         false,
         false,
         Some((second_anchoring.0, second_anchoring.1, false)),
-        None,
+        vec![],
     );
 
     let first_transaction = build_and_sign_transaction(
@@ -1387,13 +1408,13 @@ fn counter_init_and_two_inc_tx_anchored_fail_2nd_succeed() {
 
     println!("First Account data {:?}", final_first_account_data);
 
-    assert_eq!(final_first_account_data, CounterData::new(1, 1));
+    assert_eq!(final_first_account_data.value_and_step(), (1, 1));
 
     let final_second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 
     println!("First Account data {:?}", final_second_account_data);
 
-    assert_eq!(final_second_account_data, CounterData::new(2, 1));
+    assert_eq!(final_second_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -1458,7 +1479,7 @@ fn counter_init_and_two_inc_tx_anchored_fail_2nd_state_only_succeed() {
         false,
         false,
         Some((first_anchoring.0, first_anchoring.1, true)),
-        None,
+        vec![],
     );
 
     let second_increase_instruction = get_counter_increase_instruction(
@@ -1468,7 +1489,7 @@ fn counter_init_and_two_inc_tx_anchored_fail_2nd_state_only_succeed() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let first_transaction = build_and_sign_transaction(
@@ -1544,11 +1565,670 @@ fn counter_init_and_two_inc_tx_anchored_fail_2nd_state_only_succeed() {
 
     println!("First Account data {:?}", final_first_account_data);
 
-    assert_eq!(final_first_account_data, CounterData::new(1, 1));
+    assert_eq!(final_first_account_data.value_and_step(), (1, 1));
 
     let final_second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 
     println!("First Account data {:?}", final_second_account_data);
 
-    assert_eq!(final_second_account_data, CounterData::new(2, 1));
+    assert_eq!(final_second_account_data.value_and_step(), (2, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_wrong_authority_fail() {
+    println!("Counter Increase Failure ( Increase signed by an account that is not the counter authority )",);
+    println!(
+        "Initializing the counter to (1,1) with a first authority, then increasing it with a second authority, the state shouldn't be updated"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (first_authority_keypair, _, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&first_authority_keypair)
+        .unwrap();
+
+    let (second_authority_keypair, second_authority_pubkey, _) =
+        generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&second_authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            first_authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &first_authority_keypair).unwrap();
+
+    let increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &second_authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let increase_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(second_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, second_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(increase_transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert!(matches!(
+        processed_transaction.status,
+        Status::Failed { .. }
+    ));
+
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::InvalidAuthority)
+    );
+
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_authority_not_signer_fail() {
+    println!("Counter Increase Failure ( Counter authority passed without signing )",);
+    println!(
+        "Initializing the counter to (1,1), then increasing it with the right authority account marked as a non signer, the state shouldn't be updated"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (first_authority_keypair, first_authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&first_authority_keypair)
+        .unwrap();
+
+    let (second_authority_keypair, second_authority_pubkey, _) =
+        generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&second_authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            first_authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &first_authority_keypair).unwrap();
+
+    let mut increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &first_authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    increase_istruction.accounts[1].is_signer = false;
+
+    let increase_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(second_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, second_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(increase_transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert!(matches!(
+        processed_transaction.status,
+        Status::Failed { .. }
+    ));
+
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_transfer_authority_old_authority_fail() {
+    println!(
+        "Counter Authority Transfer ( Previous authority can no longer increase the counter )",
+    );
+    println!(
+        "Initializing the counter to (1,1), transferring its authority to a second account, then increasing it with the first authority, which should fail, and with the second one, which should succeed"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (first_authority_keypair, first_authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&first_authority_keypair)
+        .unwrap();
+
+    let (second_authority_keypair, second_authority_pubkey, _) =
+        generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&second_authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            first_authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &first_authority_keypair).unwrap();
+
+    let transfer_authority_instruction = instruction::transfer_authority(
+        &program_pubkey,
+        &account_pubkey,
+        &first_authority_pubkey,
+        &second_authority_pubkey,
+    );
+
+    let transfer_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[transfer_authority_instruction],
+            Some(first_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, first_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transfer_transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(account_data.authority, second_authority_pubkey);
+
+    let old_authority_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &first_authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let old_authority_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[old_authority_increase_istruction],
+            Some(first_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, first_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(old_authority_transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(
+        processed_transaction.status,
+        Status::Failed { .. }
+    ));
+
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::InvalidAuthority)
+    );
+
+    assert_eq!(
+        get_account_counter(&account_pubkey)
+            .unwrap()
+            .value_and_step(),
+        (1, 1)
+    );
+
+    let new_authority_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &second_authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let new_authority_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[new_authority_increase_istruction],
+            Some(second_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, second_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(new_authority_transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
+}
+
+/// Initializes a counter to (1,1), sends an increase failing as described by `fault`, optionally
+/// anchored, and checks the transaction failed without touching the state
+fn assert_counter_inc_fault_rolls_back(fault: FaultPlan, anchored: bool) {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let anchoring = if anchored {
+        let anchoring = generate_anchoring(&account_pubkey);
+        Some((anchoring.0, anchoring.1, false).into())
+    } else {
+        None
+    };
+
+    let increase_istruction = instruction::increase(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        fault,
+        anchoring,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!("processed_transaction {:?}", processed_transaction);
+
+    assert!(matches!(
+        processed_transaction.status,
+        Status::Failed { .. }
+    ));
+
+    if let (
+        FaultPlan::Fail {
+            action: FaultAction::ReturnError(code),
+            ..
+        },
+        Status::Failed(message),
+    ) = (fault, &processed_transaction.status)
+    {
+        assert_eq!(parse_custom_error_code(message), Some(code));
+    }
+    assert!(processed_transaction.bitcoin_txid.is_none());
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_fault_before_state_write() {
+    assert_counter_inc_fault_rolls_back(
+        FaultPlan::Fail {
+            stage: FaultStage::BeforeStateWrite,
+            action: FaultAction::ReturnError(42),
+        },
+        false,
+    );
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_fault_after_state_write() {
+    assert_counter_inc_fault_rolls_back(
+        FaultPlan::Fail {
+            stage: FaultStage::AfterStateWrite,
+            action: FaultAction::ReturnError(42),
+        },
+        false,
+    );
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_anchored_fault_after_anchoring_deserialization() {
+    assert_counter_inc_fault_rolls_back(
+        FaultPlan::Fail {
+            stage: FaultStage::AfterAnchoringDeserialization,
+            action: FaultAction::Panic,
+        },
+        true,
+    );
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_anchored_fault_after_set_transaction_to_sign() {
+    assert_counter_inc_fault_rolls_back(
+        FaultPlan::Fail {
+            stage: FaultStage::AfterSetTransactionToSign,
+            action: FaultAction::ReturnError(42),
+        },
+        true,
+    );
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_inc_anchored_fault_exhaust_compute() {
+    assert_counter_inc_fault_rolls_back(
+        FaultPlan::Fail {
+            stage: FaultStage::AfterStateWrite,
+            action: FaultAction::ExhaustCompute,
+        },
+        true,
+    );
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_migrate_current_layout_fail() {
+    println!("Counter Migration Failure ( Migrating a counter already using the current layout )",);
+    println!(
+        "Initializing the counter to (1,1) with the current layout, then migrating it, the instruction should fail and the state shouldn't change"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let migrate_instruction =
+        instruction::migrate(&program_pubkey, &account_pubkey, &authority_pubkey);
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[migrate_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::AlreadyMigrated)
+    );
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(final_account_data.version, COUNTER_DATA_VERSION);
+    assert_eq!(final_account_data.value_and_step(), (1, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_batch_increase_foreign_account_fail() {
+    println!("Counter Batch Increase Failure ( Batch including an account that is not a counter )",);
+    println!(
+        "Initializing two counters to (1,1), then increasing them in a batch also listing a wallet account, the instruction should fail and no counter should be updated"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let (wallet_keypair, wallet_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&wallet_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let counters: Vec<_> = (0..2)
+        .map(|_| start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap())
+        .collect();
+    let account_pubkeys = [counters[0].0, wallet_pubkey, counters[1].0];
+
+    let batch_instruction = instruction::batch_increase(
+        &program_pubkey,
+        &account_pubkeys,
+        &authority_pubkey,
+        None,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[batch_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![
+            counters[0].1,
+            wallet_keypair,
+            counters[1].1,
+            authority_keypair,
+        ],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::InvalidBatchAccount)
+    );
+
+    for (account_pubkey, _) in &counters {
+        assert_eq!(
+            get_account_counter(account_pubkey)
+                .unwrap()
+                .value_and_step(),
+            (1, 1)
+        );
+    }
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_close_to_itself_fail() {
+    println!("Counter Close Failure ( Closing a counter with itself as the destination )",);
+    println!(
+        "Initializing the counter to (1,1), then closing it into itself, the instruction should fail and the counter should still hold its lamports and state"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let initial_lamports = client.read_account_info(account_pubkey).unwrap().lamports;
+
+    let close_instruction = instruction::close(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        &account_pubkey,
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[close_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert_eq!(
+        counter_error_from_status(&processed_transaction.status),
+        Some(CounterError::InvalidCloseDestination)
+    );
+
+    let account_info = client.read_account_info(account_pubkey).unwrap();
+
+    assert_eq!(account_info.lamports, initial_lamports);
+    assert_eq!(account_info.owner, program_pubkey);
+    assert_eq!(
+        get_account_counter(&account_pubkey)
+            .unwrap()
+            .value_and_step(),
+        (1, 1)
+    );
 }
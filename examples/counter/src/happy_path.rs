@@ -1,12 +1,19 @@
 use std::str::FromStr;
 
 use crate::{
-    counter_helpers::{generate_anchoring, get_account_counter},
-    counter_instructions::{get_counter_increase_instruction, start_new_counter, CounterData},
+    counter_errors::counter_error_from_status,
+    counter_helpers::{
+        address_output, generate_anchoring, get_account_counter, parse_compute_unit_profiles,
+    },
+    counter_instructions::{
+        get_counter_decrease_instruction, get_counter_increase_instruction, start_legacy_counter,
+        start_named_counter, start_new_counter, start_new_counter_with_policy,
+    },
     rollback_tests::mine_block,
-    AUTHORITY_FILE_PATH, ELF_PATH, PROGRAM_FILE_PATH,
+    ANCHORING_OUTPUT_ADDRESS, AUTHORITY_FILE_PATH, ELF_PATH, INCREASE_ANCHORED_COMPUTE_BUDGET,
+    PROGRAM_FILE_PATH,
 };
-use arch_program::sanitized::ArchMessage;
+use arch_program::{pubkey::Pubkey, sanitized::ArchMessage};
 
 use arch_sdk::blocking::{ArchRpcClient, ProgramDeployer};
 use arch_sdk::{
@@ -14,6 +21,12 @@ use arch_sdk::{
 };
 
 use bitcoincore_rpc::{Auth, Client, RpcApi};
+use counter_interface::{
+    error::CounterError,
+    find_counter_address,
+    instruction::{self, Anchoring},
+    state::{CounterData, OverflowPolicy, COUNTER_DATA_LEGACY_VERSION, COUNTER_DATA_VERSION},
+};
 use serial_test::serial;
 
 #[ignore]
@@ -113,7 +126,7 @@ fn counter_init_and_inc_test() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -132,7 +145,7 @@ fn counter_init_and_inc_test() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(2, 1));
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -176,7 +189,7 @@ fn counter_init_and_inc_transaction_test() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_istruction = get_counter_increase_instruction(
@@ -186,7 +199,7 @@ fn counter_init_and_inc_transaction_test() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -205,7 +218,7 @@ fn counter_init_and_inc_transaction_test() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(3, 1));
+    assert_eq!(final_account_data.value_and_step(), (3, 1));
 }
 
 #[ignore]
@@ -248,7 +261,7 @@ fn counter_init_and_inc_block_test() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let first_transaction = build_and_sign_transaction(
@@ -269,7 +282,7 @@ fn counter_init_and_inc_block_test() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_transaction = build_and_sign_transaction(
@@ -296,7 +309,7 @@ fn counter_init_and_inc_block_test() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(3, 1));
+    assert_eq!(final_account_data.value_and_step(), (3, 1));
 }
 
 #[ignore]
@@ -347,7 +360,7 @@ fn counter_init_and_inc_anchored() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        Some(2500),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
     );
 
     let transaction = build_and_sign_transaction(
@@ -392,7 +405,7 @@ fn counter_init_and_inc_anchored() {
 
     let final_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(final_account_data, CounterData::new(2, 1));
+    assert_eq!(final_account_data.value_and_step(), (2, 1));
 
     let second_increase_istruction = get_counter_increase_instruction(
         &program_pubkey,
@@ -401,7 +414,7 @@ fn counter_init_and_inc_anchored() {
         false,
         false,
         Some((anchoring.0, anchoring.1, false)),
-        None,
+        vec![],
     );
 
     let second_transaction = build_and_sign_transaction(
@@ -446,3 +459,754 @@ fn counter_init_and_inc_anchored() {
         )
         .unwrap();
 }
+
+fn increase_counter_past_u64_max(overflow_policy: OverflowPolicy) -> (Status, CounterData) {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) = start_new_counter_with_policy(
+        &program_pubkey,
+        2,
+        u64::MAX - 3,
+        overflow_policy,
+        &authority_keypair,
+    )
+    .unwrap();
+
+    let first_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[first_increase_istruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+    assert_eq!(
+        get_account_counter(&account_pubkey)
+            .unwrap()
+            .value_and_step(),
+        (u64::MAX - 1, 2)
+    );
+
+    let second_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[second_increase_istruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!("Transaction status : {:?}", processed_transaction.status);
+
+    (
+        processed_transaction.status,
+        get_account_counter(&account_pubkey).unwrap(),
+    )
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_overflow_error_policy() {
+    println!("Counter Overflow ( Error policy )",);
+    println!(
+        "Initializing the counter to (u64::MAX - 3, 2) with the Error policy, then increasing it past u64::MAX, the instruction should fail and the state shouldn't change"
+    );
+
+    let (status, final_account_data) = increase_counter_past_u64_max(OverflowPolicy::Error);
+
+    assert_eq!(
+        counter_error_from_status(&status),
+        Some(CounterError::Overflow)
+    );
+    assert_eq!(final_account_data.value_and_step(), (u64::MAX - 1, 2));
+    assert_eq!(final_account_data.overflow_policy, OverflowPolicy::Error);
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_overflow_wrap_policy() {
+    println!("Counter Overflow ( Wrap policy )",);
+    println!(
+        "Initializing the counter to (u64::MAX - 3, 2) with the Wrap policy, then increasing it past u64::MAX, the value should wrap around to 0"
+    );
+
+    let (status, final_account_data) = increase_counter_past_u64_max(OverflowPolicy::Wrap);
+
+    assert!(matches!(status, Status::Processed));
+    assert_eq!(final_account_data.value_and_step(), (0, 2));
+    assert_eq!(final_account_data.overflow_policy, OverflowPolicy::Wrap);
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_overflow_saturate_policy() {
+    println!("Counter Overflow ( Saturate policy )",);
+    println!(
+        "Initializing the counter to (u64::MAX - 3, 2) with the Saturate policy, then increasing it past u64::MAX, the value should stay at u64::MAX"
+    );
+
+    let (status, final_account_data) = increase_counter_past_u64_max(OverflowPolicy::Saturate);
+
+    assert!(matches!(status, Status::Processed));
+    assert_eq!(final_account_data.value_and_step(), (u64::MAX, 2));
+    assert_eq!(final_account_data.overflow_policy, OverflowPolicy::Saturate);
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_set_step_reset_and_decrease_test() {
+    println!("Counter SetStep, Reset and Decrease ( Three instructions in one transaction, then two separate decreases )",);
+    println!(
+        "Initializing the counter to (1,1), setting the step to 5, resetting the value to 10 and decreasing it, then decreasing it below zero, the last decrease should fail"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let set_step_instruction =
+        instruction::set_step(&program_pubkey, &account_pubkey, &authority_pubkey, 5);
+
+    let reset_instruction =
+        instruction::reset(&program_pubkey, &account_pubkey, &authority_pubkey, 10);
+
+    let decrease_instruction = get_counter_decrease_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[
+                set_step_instruction,
+                reset_instruction,
+                decrease_instruction,
+            ],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+    assert_eq!(
+        get_account_counter(&account_pubkey)
+            .unwrap()
+            .value_and_step(),
+        (5, 5)
+    );
+
+    let mut statuses = vec![];
+
+    for _ in 0..2 {
+        let decrease_instruction = get_counter_decrease_instruction(
+            &program_pubkey,
+            &account_pubkey,
+            &authority_pubkey,
+            false,
+            false,
+            None,
+            vec![],
+        );
+
+        let transaction = build_and_sign_transaction(
+            ArchMessage::new(
+                &[decrease_instruction],
+                Some(authority_pubkey),
+                client.get_best_finalized_block_hash().unwrap(),
+            ),
+            vec![account_keypair, authority_keypair],
+            config.network,
+        )
+        .expect("Failed to build and sign transaction");
+
+        let txid = client.send_transaction(transaction).unwrap();
+        let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+        statuses.push(processed_transaction.status);
+    }
+
+    assert!(matches!(statuses[0], Status::Processed));
+    assert_eq!(
+        counter_error_from_status(&statuses[1]),
+        Some(CounterError::Underflow)
+    );
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(final_account_data.value_and_step(), (0, 5));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_migration_test() {
+    println!("Counter Migration ( Upgrading a counter stored with the 4 bytes v1 layout )",);
+    println!(
+        "Creating a counter holding the v1 layout for (0,0), migrating it, then setting the step to 3 and increasing it, the counter should end at (3,3) under the signing authority"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_legacy_counter(&program_pubkey, &authority_keypair).unwrap();
+
+    let legacy_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(legacy_account_data.version, COUNTER_DATA_LEGACY_VERSION);
+    assert_eq!(legacy_account_data.value_and_step(), (0, 0));
+
+    let migrate_instruction =
+        instruction::migrate(&program_pubkey, &account_pubkey, &authority_pubkey);
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[migrate_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let migrated_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(
+        migrated_account_data,
+        CounterData::new(0, 0, OverflowPolicy::Error, authority_pubkey)
+    );
+
+    let set_step_instruction =
+        instruction::set_step(&program_pubkey, &account_pubkey, &authority_pubkey, 3);
+
+    let increase_instruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[set_step_instruction, increase_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let final_account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(final_account_data.version, COUNTER_DATA_VERSION);
+    assert_eq!(final_account_data.value_and_step(), (3, 3));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_close_test() {
+    println!("Counter Close ( Closing the counter to a destination account, then increasing it )",);
+    println!(
+        "Initializing the counter to (1,1), closing it, the destination should receive the counter lamports and the next increase should fail"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let (destination_keypair, destination_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&destination_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let counter_lamports = client.read_account_info(account_pubkey).unwrap().lamports;
+    let destination_lamports = client
+        .read_account_info(destination_pubkey)
+        .unwrap()
+        .lamports;
+
+    let close_instruction = instruction::close(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        &destination_pubkey,
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[close_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let closed_account = client.read_account_info(account_pubkey).unwrap();
+
+    assert_eq!(closed_account.lamports, 0);
+    assert!(closed_account.data.is_empty());
+    assert_eq!(closed_account.owner, Pubkey::system_program());
+    assert_eq!(
+        client
+            .read_account_info(destination_pubkey)
+            .unwrap()
+            .lamports,
+        destination_lamports + counter_lamports
+    );
+
+    let increase_instruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        None,
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!("Transaction status : {:?}", processed_transaction.status);
+
+    assert!(matches!(
+        &processed_transaction.status,
+        Status::Failed(message) if message.contains("UninitializedAccount")
+    ));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_named_initialization_and_increase_test() {
+    println!("Named Counter Initialization and Increase ( Counter at a program derived address )",);
+    println!(
+        "Initializing a counter named after its owner to (1,1), finding it again from the owner key and its name, then increasing it"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (owner_keypair, owner_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&owner_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            owner_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let account_pubkey =
+        start_named_counter(&program_pubkey, "visits", 1, 1, &owner_keypair).unwrap();
+
+    let (found_account_pubkey, _) = find_counter_address(&owner_pubkey, "visits", &program_pubkey);
+
+    assert_eq!(found_account_pubkey, account_pubkey);
+
+    let increase_instruction =
+        instruction::without_counter_signature(get_counter_increase_instruction(
+            &program_pubkey,
+            &found_account_pubkey,
+            &owner_pubkey,
+            false,
+            false,
+            None,
+            vec![],
+        ));
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_instruction],
+            Some(owner_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![owner_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+    assert_eq!(
+        get_account_counter(&found_account_pubkey)
+            .unwrap()
+            .value_and_step(),
+        (2, 1)
+    );
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_increase_by_and_batch_increase_test() {
+    println!("Counter IncreaseBy and Batch Increase ( Three counters anchored in one Bitcoin transaction )",);
+    println!(
+        "Initializing three counters to (1,1), increasing the first one by 10, then increasing all of them in a single anchored instruction"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let counters: Vec<_> = (0..3)
+        .map(|_| start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap())
+        .collect();
+    let account_pubkeys: Vec<_> = counters.iter().map(|(pubkey, _)| *pubkey).collect();
+
+    let increase_by_instruction =
+        instruction::increase_by(&program_pubkey, &account_pubkeys[0], &authority_pubkey, 10);
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_by_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![counters[0].1, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+    assert_eq!(
+        get_account_counter(&account_pubkeys[0])
+            .unwrap()
+            .value_and_step(),
+        (11, 1)
+    );
+
+    let anchoring = generate_anchoring(&account_pubkeys[0]);
+
+    let batch_instruction = instruction::batch_increase(
+        &program_pubkey,
+        &account_pubkeys,
+        &authority_pubkey,
+        Some(Anchoring::new(vec![anchoring.1])),
+        vec![],
+    );
+
+    let mut signers: Vec<_> = counters.iter().map(|(_, keypair)| *keypair).collect();
+    signers.push(authority_keypair);
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[batch_instruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        signers,
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!("Transaction status : {:?}", processed_transaction.status);
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let userpass = Auth::UserPass(config.node_username, config.node_password);
+    let rpc =
+        Client::new(&config.node_endpoint, userpass).expect("rpc shouldn not fail to be initiated");
+
+    let tx_info = rpc
+        .get_raw_transaction_info(
+            &bitcoin::Txid::from_str(&processed_transaction.bitcoin_txid.unwrap().to_string())
+                .unwrap(),
+            None,
+        )
+        .unwrap();
+
+    // One state transition input per counter, plus the fees input
+    assert_eq!(tx_info.vin.len(), account_pubkeys.len() + 1);
+
+    let values: Vec<_> = account_pubkeys
+        .iter()
+        .map(|account_pubkey| {
+            get_account_counter(account_pubkey)
+                .unwrap()
+                .value_and_step()
+        })
+        .collect();
+
+    assert_eq!(values, vec![(12, 1), (2, 1), (2, 1)]);
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn counter_increase_compute_budget_test() {
+    println!("Counter Increase Compute Budget",);
+    println!(
+        "Happy Path Scenario : Initializing the counter to (1,1), then increasing it with anchoring, the instruction should stay within its compute budget"
+    );
+
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let anchoring = generate_anchoring(&account_pubkey);
+
+    let increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        Some((anchoring.0, anchoring.1, false)),
+        vec![],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let profiles = parse_compute_unit_profiles(&processed_transaction.logs);
+
+    println!("Compute unit profiles : {:?}", profiles);
+
+    assert_eq!(profiles.len(), 1);
+
+    let profile = &profiles[0];
+
+    // Both `sol_log_compute_units` checkpoints are reached when anchoring
+    assert_eq!(profile.remaining.len(), 2);
+
+    println!(
+        "Units consumed between checkpoints : {:?}",
+        profile.deltas()
+    );
+
+    let consumed = profile
+        .consumed
+        .expect("the runtime should log the consumed compute units");
+
+    assert!(
+        consumed <= INCREASE_ANCHORED_COMPUTE_BUDGET,
+        "anchored increase consumed {} compute units, over the {} budget",
+        consumed,
+        INCREASE_ANCHORED_COMPUTE_BUDGET
+    );
+}
@@ -53,7 +53,7 @@ fn test_intra_block_tx_cache() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        None,
+        vec![],
     );
 
     let second_increase_istruction = get_counter_increase_instruction(
@@ -63,7 +63,7 @@ fn test_intra_block_tx_cache() {
         false,
         false,
         Some((second_anchoring.0, second_anchoring.1.clone(), false)),
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -1,11 +1,16 @@
 #![cfg(test)]
-pub const ELF_PATH: &str = "./program/target/sbpf-solana-solana/release/counter_program.so";
+pub const ELF_PATH: &str = "./target/sbpf-solana-solana/release/counter_program.so";
 
 const PROGRAM_FILE_PATH: &str = ".counter_program.json";
 const AUTHORITY_FILE_PATH: &str = ".counter_authority.json";
 
 const MINING_ADDRESS: &str = "bcrt1q9s6pf9hswah20jjnzmyvk9s2xwp7srz6m2r5tw";
+const ANCHORING_OUTPUT_ADDRESS: &str = "bcrt1q9lu00cj3y0qzm6wqr6nr46s877259uz9r802sm";
 
+/// Compute units an anchored `IncreaseCounter` may consume before `counter_increase_compute_budget_test` fails
+const INCREASE_ANCHORED_COMPUTE_BUDGET: u64 = 50_000;
+
+pub mod counter_errors;
 pub mod counter_helpers;
 pub mod counter_instructions;
 #[cfg(test)]
@@ -54,7 +54,7 @@ fn test_state_block_exists() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let increase_transaction = build_and_sign_transaction(
@@ -118,7 +118,7 @@ fn test_state_block_pruned() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let first_increase_transaction = build_and_sign_transaction(
@@ -152,7 +152,7 @@ fn test_state_block_pruned() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let second_increase_transaction = build_and_sign_transaction(
@@ -3,7 +3,7 @@
 // /* -------------------------------------------------------------------------- */
 use std::{str::FromStr, thread, time::Duration};
 
-use arch_program::sanitized::ArchMessage;
+use arch_program::{pubkey::Pubkey, sanitized::ArchMessage};
 use arch_sdk::blocking::{ArchRpcClient, ProgramDeployer};
 use arch_sdk::{
     build_and_sign_transaction, generate_new_keypair, with_secret_key_file, Config, Status,
@@ -11,12 +11,13 @@ use arch_sdk::{
 
 use bitcoin::{address::NetworkChecked, Address, BlockHash, Network, Txid};
 use bitcoincore_rpc::{Auth, Client, RpcApi};
+use counter_interface::instruction::{self, Anchoring};
 use serial_test::serial;
 
 use crate::{
-    counter_helpers::{generate_anchoring, get_account_counter},
-    counter_instructions::{get_counter_increase_instruction, start_new_counter, CounterData},
-    ELF_PATH, MINING_ADDRESS, PROGRAM_FILE_PATH,
+    counter_helpers::{address_output, generate_anchoring, get_account_counter},
+    counter_instructions::{get_counter_increase_instruction, start_new_counter},
+    ANCHORING_OUTPUT_ADDRESS, ELF_PATH, MINING_ADDRESS, PROGRAM_FILE_PATH,
 };
 
 pub const WAIT_FOR_ROLLBACK: u8 = 10;
@@ -191,7 +192,7 @@ fn single_utxo_rbf_two_accounts() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        Some(2500),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
     );
 
     let transaction = build_and_sign_transaction(
@@ -222,7 +223,7 @@ fn single_utxo_rbf_two_accounts() {
 
     let first_account_data = get_account_counter(&first_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(2, 1));
+    assert_eq!(first_account_data.value_and_step(), (2, 1));
 
     println!("Increasing the second counter using the same unique utxo",);
 
@@ -233,7 +234,7 @@ fn single_utxo_rbf_two_accounts() {
         false,
         false,
         Some((anchoring.0, anchoring.1, false)),
-        None,
+        vec![],
     );
 
     let second_transaction = build_and_sign_transaction(
@@ -268,8 +269,8 @@ fn single_utxo_rbf_two_accounts() {
 
     let second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(1, 1));
-    assert_eq!(second_account_data, CounterData::new(2, 1));
+    assert_eq!(first_account_data.value_and_step(), (1, 1));
+    assert_eq!(second_account_data.value_and_step(), (2, 1));
 }
 
 #[ignore]
@@ -348,7 +349,7 @@ fn single_utxo_rbf_three_accounts() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        Some(5000),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 5000)],
     );
 
     let transaction = build_and_sign_transaction(
@@ -378,7 +379,7 @@ fn single_utxo_rbf_three_accounts() {
 
     let first_account_data = get_account_counter(&account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(2, 1));
+    assert_eq!(first_account_data.value_and_step(), (2, 1));
 
     println!("Increasing the second counter using the same unique utxo");
 
@@ -389,7 +390,7 @@ fn single_utxo_rbf_three_accounts() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        Some(2500),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
     );
 
     let second_transaction = build_and_sign_transaction(
@@ -425,7 +426,7 @@ fn single_utxo_rbf_three_accounts() {
         false,
         false,
         Some((anchoring.0, anchoring.1, false)),
-        None,
+        vec![],
     );
 
     let third_transaction = build_and_sign_transaction(
@@ -463,9 +464,9 @@ fn single_utxo_rbf_three_accounts() {
 
     let third_account_data = get_account_counter(&third_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(1, 1));
-    assert_eq!(second_account_data, CounterData::new(1, 1));
-    assert_eq!(third_account_data, CounterData::new(2, 1));
+    assert_eq!(first_account_data.value_and_step(), (1, 1));
+    assert_eq!(second_account_data.value_and_step(), (1, 1));
+    assert_eq!(third_account_data.value_and_step(), (2, 1));
 
     //let second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 }
@@ -537,7 +538,7 @@ fn rbf_orphan_arch_txs() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        Some(2500),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
     );
 
     let transaction = build_and_sign_transaction(
@@ -568,7 +569,7 @@ fn rbf_orphan_arch_txs() {
 
     let first_account_data = get_account_counter(&first_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(2, 1));
+    assert_eq!(first_account_data.value_and_step(), (2, 1));
 
     println!("Increasing the first counter again without anchoring");
 
@@ -579,7 +580,7 @@ fn rbf_orphan_arch_txs() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -610,7 +611,7 @@ fn rbf_orphan_arch_txs() {
 
     let first_account_data = get_account_counter(&first_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(3, 1));
+    assert_eq!(first_account_data.value_and_step(), (3, 1));
 
     println!("Increasing the second counter using the same unique utxo",);
 
@@ -621,7 +622,7 @@ fn rbf_orphan_arch_txs() {
         false,
         false,
         Some((anchoring.0, anchoring.1, false)),
-        None,
+        vec![],
     );
 
     let second_transaction = build_and_sign_transaction(
@@ -656,9 +657,9 @@ fn rbf_orphan_arch_txs() {
 
     let second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(1, 1));
+    assert_eq!(first_account_data.value_and_step(), (1, 1));
 
-    assert_eq!(second_account_data, CounterData::new(2, 1));
+    assert_eq!(second_account_data.value_and_step(), (2, 1));
 
     //let second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 }
@@ -732,7 +733,7 @@ fn rbf_reorg() {
         false,
         false,
         Some((anchoring.0, anchoring.1.clone(), false)),
-        Some(2500),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
     );
 
     let transaction = build_and_sign_transaction(
@@ -766,8 +767,8 @@ fn rbf_reorg() {
     let first_account_data = get_account_counter(&first_account_pubkey).unwrap();
     let second_account_data = get_account_counter(&second_account_pubkey).unwrap();
 
-    assert_eq!(first_account_data, CounterData::new(2, 1));
-    assert_eq!(second_account_data, CounterData::new(1, 1));
+    assert_eq!(first_account_data.value_and_step(), (2, 1));
+    assert_eq!(second_account_data.value_and_step(), (1, 1));
     println!("First account data : {:?}", first_account_data);
     println!("Second account data : {:?}", second_account_data);
 
@@ -794,7 +795,7 @@ fn rbf_reorg() {
         false,
         false,
         Some((anchoring.0, anchoring.1, false)),
-        None,
+        vec![],
     );
 
     let second_transaction = build_and_sign_transaction(
@@ -834,8 +835,8 @@ fn rbf_reorg() {
         "Second account data : {:?}",
         client.read_account_info(second_account_pubkey).unwrap()
     );
-    assert_eq!(first_account_data, CounterData::new(1, 1));
-    assert_eq!(second_account_data, CounterData::new(2, 1));
+    assert_eq!(first_account_data.value_and_step(), (1, 1));
+    assert_eq!(second_account_data.value_and_step(), (2, 1));
 
     let userpass = Auth::UserPass(config.node_username, config.node_password);
     let rpc_node2 = Client::new("http://127.0.0.1:18453/wallet/testwallet", userpass.clone())
@@ -860,7 +861,7 @@ fn rbf_reorg() {
         false,
         false,
         None,
-        None,
+        vec![],
     );
 
     let transaction = build_and_sign_transaction(
@@ -901,6 +902,550 @@ fn rbf_reorg() {
         client.read_account_info(second_account_pubkey).unwrap()
     );
 
-    assert_eq!(first_account_data, CounterData::new(3, 1));
-    assert_eq!(second_account_data, CounterData::new(1, 1));
+    assert_eq!(first_account_data.value_and_step(), (3, 1));
+    assert_eq!(second_account_data.value_and_step(), (1, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn single_utxo_rbf_custom_outputs() {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    println!("2 Counters, same utxo replaced by a greater fee, caller chosen outputs",);
+    println!("Roll Back scenario : The replacing transaction pays less to its outputs, the replaced transaction should be rolled back"
+    );
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (first_authority_keypair, first_authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&first_authority_keypair)
+        .unwrap();
+
+    let (second_authority_keypair, second_authority_pubkey, _) =
+        generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&second_authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            first_authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (first_account_pubkey, first_account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &first_authority_keypair).unwrap();
+
+    let (second_account_pubkey, second_account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &second_authority_keypair).unwrap();
+
+    let anchoring = generate_anchoring(&first_account_pubkey);
+
+    let _ = mine_block();
+
+    println!("Increasing the first counter, paying two outputs");
+
+    let increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &first_account_pubkey,
+        &first_authority_pubkey,
+        false,
+        false,
+        Some((anchoring.0, anchoring.1.clone(), false)),
+        vec![
+            address_output(MINING_ADDRESS, 2500),
+            address_output(ANCHORING_OUTPUT_ADDRESS, 2500),
+        ],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(first_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![first_account_keypair, first_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transactions = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(!matches!(
+        processed_transactions.status,
+        Status::Failed { .. }
+    ));
+    assert!(processed_transactions.bitcoin_txid.is_some());
+
+    println!("Increasing the second counter with the same utxo, paying a single smaller output");
+
+    let replacing_output = address_output(ANCHORING_OUTPUT_ADDRESS, 1000);
+
+    let second_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &second_account_pubkey,
+        &second_authority_pubkey,
+        false,
+        false,
+        Some((anchoring.0, anchoring.1, false)),
+        vec![replacing_output.clone()],
+    );
+
+    let second_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[second_increase_istruction],
+            Some(second_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![second_account_keypair, second_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(second_transaction).unwrap();
+    let second_processed_transactions = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(!matches!(
+        second_processed_transactions.status,
+        Status::Failed { .. }
+    ));
+
+    let _ = mine_block();
+
+    thread::sleep(std::time::Duration::from_secs(10));
+
+    let first_account_data = get_account_counter(&first_account_pubkey).unwrap();
+    let second_account_data = get_account_counter(&second_account_pubkey).unwrap();
+
+    assert_eq!(first_account_data.value_and_step(), (1, 1));
+    assert_eq!(second_account_data.value_and_step(), (2, 1));
+
+    let userpass = Auth::UserPass(config.node_username, config.node_password);
+    let rpc =
+        Client::new(&config.node_endpoint, userpass).expect("rpc shouldn not fail to be initiated");
+
+    let second_txid = Txid::from_str(
+        &second_processed_transactions
+            .bitcoin_txid
+            .unwrap()
+            .to_string(),
+    )
+    .unwrap();
+    let second_tx = rpc.get_raw_transaction(&second_txid, None).unwrap();
+
+    assert!(second_tx.output.iter().any(|output| {
+        output.script_pubkey.as_bytes() == replacing_output.0.as_slice()
+            && output.value.to_sat() == replacing_output.1
+    }));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn anchoring_dust_output_fail() {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    println!("Anchored increase paying a dust output, the instruction should fail and the state stay untouched");
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (authority_keypair, authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (account_pubkey, account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &authority_keypair).unwrap();
+
+    let anchoring = generate_anchoring(&account_pubkey);
+
+    let _ = mine_block();
+
+    let increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &account_pubkey,
+        &authority_pubkey,
+        false,
+        false,
+        Some((anchoring.0, anchoring.1, false)),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 1)],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![account_keypair, authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transactions = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(
+        processed_transactions.status,
+        Status::Failed { .. }
+    ));
+    assert!(processed_transactions.bitcoin_txid.is_none());
+
+    let account_data = get_account_counter(&account_pubkey).unwrap();
+
+    assert_eq!(account_data.value_and_step(), (1, 1));
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn single_utxo_rbf_history() {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    println!(
+        "2 Counters, same utxo replaced by a greater fee, first counter recording its history",
+    );
+    println!("Roll Back scenario : The replaced increase should disappear from the first counter history"
+    );
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (first_authority_keypair, first_authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&first_authority_keypair)
+        .unwrap();
+
+    let (second_authority_keypair, second_authority_pubkey, _) =
+        generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&second_authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            first_authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let (first_account_pubkey, first_account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &first_authority_keypair).unwrap();
+
+    let (second_account_pubkey, second_account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &second_authority_keypair).unwrap();
+
+    let enable_history_instruction = instruction::enable_history(
+        &program_pubkey,
+        &first_account_pubkey,
+        &first_authority_pubkey,
+        4,
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[enable_history_instruction],
+            Some(first_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![first_account_keypair, first_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transactions = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(matches!(processed_transactions.status, Status::Processed));
+
+    let anchoring = generate_anchoring(&first_account_pubkey);
+
+    let _ = mine_block();
+
+    println!("Increasing the first counter using the unique utxo");
+
+    let increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &first_account_pubkey,
+        &first_authority_pubkey,
+        false,
+        false,
+        Some((anchoring.0, anchoring.1.clone(), false)),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
+    );
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[increase_istruction],
+            Some(first_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![first_account_keypair, first_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transactions = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(!matches!(
+        processed_transactions.status,
+        Status::Failed { .. }
+    ));
+
+    let history_values = |account_pubkey: &Pubkey| -> Vec<u64> {
+        get_account_counter(account_pubkey)
+            .unwrap()
+            .history_entries()
+            .iter()
+            .map(|entry| entry.value)
+            .collect()
+    };
+
+    assert_eq!(history_values(&first_account_pubkey), vec![1, 2]);
+
+    println!("Increasing the second counter using the same unique utxo, with a greater fee",);
+
+    let second_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &second_account_pubkey,
+        &second_authority_pubkey,
+        false,
+        false,
+        Some((anchoring.0, anchoring.1, false)),
+        vec![],
+    );
+
+    let second_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[second_increase_istruction],
+            Some(second_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![second_account_keypair, second_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(second_transaction).unwrap();
+    let second_processed_transactions = client.wait_for_processed_transaction(&txid).unwrap();
+
+    assert!(!matches!(
+        second_processed_transactions.status,
+        Status::Failed { .. }
+    ));
+
+    let _ = mine_block();
+
+    thread::sleep(std::time::Duration::from_secs(10));
+
+    let first_account_data = get_account_counter(&first_account_pubkey).unwrap();
+
+    assert_eq!(first_account_data.value_and_step(), (1, 1));
+    assert_eq!(history_values(&first_account_pubkey), vec![1]);
+    assert!(history_values(&second_account_pubkey).is_empty());
+}
+
+#[ignore]
+#[serial]
+#[test]
+fn multi_input_rbf_batch() {
+    let config = Config::localnet();
+    let client = ArchRpcClient::new(&config);
+
+    println!("3 Counters, one of the two fee inputs of a batch replaced by a greater fee",);
+    println!("Roll Back scenario : Two counters are anchored together with two fee transactions, a third counter spends one of their inputs again, both batched counters should be rolled back"
+    );
+
+    let (program_keypair, _) =
+        with_secret_key_file(PROGRAM_FILE_PATH).expect("getting caller info should not fail");
+
+    let (first_authority_keypair, first_authority_pubkey, _) = generate_new_keypair(config.network);
+    client
+        .create_and_fund_program_authority_with_faucet(&first_authority_keypair)
+        .unwrap();
+
+    let (second_authority_keypair, second_authority_pubkey, _) =
+        generate_new_keypair(config.network);
+    client
+        .create_and_fund_account_with_faucet(&second_authority_keypair)
+        .unwrap();
+
+    let deployer = ProgramDeployer::new(&config);
+
+    let program_pubkey = deployer
+        .try_deploy_program(
+            "E2E-Counter".to_string(),
+            program_keypair,
+            first_authority_keypair,
+            &ELF_PATH.to_string(),
+        )
+        .unwrap();
+
+    let batched_counters: Vec<_> = (0..2)
+        .map(|_| start_new_counter(&program_pubkey, 1, 1, &first_authority_keypair).unwrap())
+        .collect();
+
+    let batched_pubkeys: Vec<_> = batched_counters
+        .iter()
+        .map(|(account_pubkey, _)| *account_pubkey)
+        .collect();
+
+    let (third_account_pubkey, third_account_keypair) =
+        start_new_counter(&program_pubkey, 1, 1, &second_authority_keypair).unwrap();
+
+    let first_anchoring = generate_anchoring(&batched_pubkeys[0]);
+    let second_anchoring = generate_anchoring(&batched_pubkeys[1]);
+
+    let btc_block_hash = mine_block();
+
+    println!();
+    println!(
+        "⛏️    Mined a new BTC Block on Regtest : {}    ⛏️  ",
+        btc_block_hash.as_raw_hash(),
+    );
+    println!();
+
+    println!("Increasing the two batched counters using both fee transactions");
+
+    let batch_instruction = instruction::batch_increase(
+        &program_pubkey,
+        &batched_pubkeys,
+        &first_authority_pubkey,
+        Some(Anchoring::new(vec![
+            first_anchoring.1,
+            second_anchoring.1.clone(),
+        ])),
+        vec![address_output(ANCHORING_OUTPUT_ADDRESS, 2500)],
+    );
+
+    let mut signers: Vec<_> = batched_counters
+        .iter()
+        .map(|(_, keypair)| *keypair)
+        .collect();
+    signers.push(first_authority_keypair);
+
+    let transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[batch_instruction],
+            Some(first_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        signers,
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+    let txid = client.send_transaction(transaction).unwrap();
+    let processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!(
+        "Batch increase processed transaction id : {}\nStatus: {:?}",
+        processed_transaction.txid(),
+        processed_transaction.status
+    );
+
+    assert!(matches!(processed_transaction.status, Status::Processed));
+
+    let userpass = Auth::UserPass(config.node_username.clone(), config.node_password.clone());
+    let rpc =
+        Client::new(&config.node_endpoint, userpass).expect("rpc shouldn not fail to be initiated");
+
+    let tx_info = rpc
+        .get_raw_transaction_info(
+            &Txid::from_str(&processed_transaction.bitcoin_txid.unwrap().to_string()).unwrap(),
+            None,
+        )
+        .unwrap();
+
+    // One state transition input per batched counter, plus one input per fee transaction
+    assert_eq!(tx_info.vin.len(), batched_pubkeys.len() + 2);
+
+    for account_pubkey in &batched_pubkeys {
+        assert_eq!(
+            get_account_counter(account_pubkey)
+                .unwrap()
+                .value_and_step(),
+            (2, 1)
+        );
+    }
+
+    println!("Increasing the third counter using only the second fee transaction");
+
+    let third_increase_istruction = get_counter_increase_instruction(
+        &program_pubkey,
+        &third_account_pubkey,
+        &second_authority_pubkey,
+        false,
+        false,
+        Some((second_anchoring.0, second_anchoring.1, false)),
+        vec![],
+    );
+
+    let third_transaction = build_and_sign_transaction(
+        ArchMessage::new(
+            &[third_increase_istruction],
+            Some(second_authority_pubkey),
+            client.get_best_finalized_block_hash().unwrap(),
+        ),
+        vec![third_account_keypair, second_authority_keypair],
+        config.network,
+    )
+    .expect("Failed to build and sign transaction");
+
+    let txid = client.send_transaction(third_transaction).unwrap();
+    let third_processed_transaction = client.wait_for_processed_transaction(&txid).unwrap();
+
+    println!(
+        "Third increase processed transaction id : {}",
+        third_processed_transaction.txid()
+    );
+
+    assert!(!matches!(
+        third_processed_transaction.status,
+        Status::Failed { .. }
+    ));
+
+    let _ = mine_block();
+
+    thread::sleep(std::time::Duration::from_secs(10));
+
+    for account_pubkey in &batched_pubkeys {
+        assert_eq!(
+            get_account_counter(account_pubkey)
+                .unwrap()
+                .value_and_step(),
+            (1, 1)
+        );
+    }
+
+    assert_eq!(
+        get_account_counter(&third_account_pubkey)
+            .unwrap()
+            .value_and_step(),
+        (2, 1)
+    );
 }
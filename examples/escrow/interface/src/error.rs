@@ -0,0 +1,57 @@
+use arch_program::program_error::ProgramError;
+
+/// Errors returned by the escrow program as `ProgramError::Custom(code)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum EscrowError {
+    /// An account is not owned by the expected program
+    InvalidOwner = 600,
+    /// The offer account is not the address derived from the maker and the offer id, or the
+    /// offer bump seed is not the one of that address
+    InvalidOfferAddress = 601,
+    /// The maker or the taker did not sign the instruction
+    MissingSigner = 602,
+    /// The offer account already holds data
+    OfferAlreadyInitialized = 603,
+    /// A mint is not the one recorded in the offer, or the offer asks for the other kind of asset
+    InvalidMint = 604,
+    /// The vault is not the offer associated token account for token A
+    InvalidVault = 605,
+    /// An account the instruction writes to is not writable
+    AccountNotWritable = 606,
+    /// The offered amount is zero, or the fill amount is zero or above what is left in the offer
+    InvalidAmount = 607,
+    /// The offer expired, it can no longer be made or taken
+    OfferExpired = 608,
+    /// The offer has no expiry or it didn't pass yet, only the maker can cancel it
+    OfferNotExpired = 609,
+    /// A token balance did not move by the expected amount during the transfers
+    BalanceMismatch = 610,
+    /// The Bitcoin payment is unknown, doesn't pay enough to the offer script, doesn't commit to
+    /// the offer in its only OP_RETURN output, or is not the payment registered on the offer
+    InvalidBitcoinPayment = 611,
+    /// The fee config is not the one of the program, is not created yet or its fee is above
+    /// `MAX_FEE_BPS`
+    InvalidFeeConfig = 612,
+    /// The fee token account is not a token B account of the fee recipient, or is the maker or
+    /// taker token B account
+    InvalidFeeAccount = 613,
+    /// The offer names another taker than the signer
+    TakerNotAllowed = 614,
+    /// A token account is not for the offer mint, or doesn't belong to the maker it pays or refunds
+    InvalidTokenAccount = 615,
+    /// A Bitcoin payment is registered on the offer, it can't be cancelled and the payment
+    /// doesn't settle it until `MIN_PAYMENT_CONFIRMATIONS` blocks were mined
+    BitcoinPaymentPending = 616,
+    /// The Bitcoin transaction of the payment is unknown or not mined, it can't be registered on
+    /// the offer or settle it
+    BitcoinPaymentUnconfirmed = 617,
+    /// The signer is not the admin who created the fee config
+    InvalidFeeAdmin = 618,
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(error: EscrowError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
@@ -0,0 +1,249 @@
+use arch_program::{
+    account::AccountMeta, instruction::Instruction, pubkey::Pubkey, utxo::UtxoMeta,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    find_associated_token_address, find_fee_config_address, find_offer_address, find_vault_address,
+    state::{Offer, OfferExpiry},
+};
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone)]
+pub struct MakeOffer {
+    /// The bump seed for the offer's Program Derived Address
+    pub offer_bump_seed: u8,
+    /// The UTXO metadata associated with this offer
+    pub offer_utxo: UtxoMeta,
+    /// Unique identifier for the offer
+    pub id: u64,
+    /// Amount of token A being offered
+    pub token_a_offered_amount: u64,
+    /// Amount of token B wanted in exchange, in sats when `btc_script_pubkey` is set
+    pub token_b_wanted_amount: u64,
+    /// Point after which the offer can no longer be taken and can be refunded by anyone
+    pub expiry: Option<OfferExpiry>,
+    /// Script the taker pays native bitcoin to instead of sending token B, the token B mint
+    /// account being the system program in that case
+    pub btc_script_pubkey: Option<Vec<u8>>,
+    /// Only taker allowed to take the offer, anyone can when `None`
+    pub allowed_taker: Option<Pubkey>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone)]
+pub struct TakeOffer {
+    /// Amount of token A taken from the vault, at most what is left in the offer
+    pub token_a_amount: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone)]
+pub struct TakeBtcOffer {
+    /// Output of the taker's Bitcoin transaction paying the offer script
+    pub payment: UtxoMeta,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone)]
+pub struct SetFeeConfig {
+    /// The UTXO the fee config account is created with, only needed the first time
+    pub fee_config_utxo: Option<UtxoMeta>,
+    /// Owner of the token B accounts receiving the fee
+    pub fee_recipient: Pubkey,
+    /// Fee in basis points of the token B paid by the taker, at most `MAX_FEE_BPS`
+    pub fee_bps: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+pub enum EscrowInstruction {
+    /// Create a new offer to exchange tokens
+    MakeOffer(MakeOffer),
+    /// Accept an existing offer, fully or partially, the last fill sweeping what the vault still
+    /// holds to the maker
+    TakeOffer(TakeOffer),
+    /// Refund the maker with the vault content and clear the offer, signed by the maker
+    CancelOffer,
+    /// Same as `CancelOffer` once the offer expired, without the maker signature so anyone can
+    /// trigger it
+    RefundExpiredOffer,
+    /// Register the taker's mined payment of a native bitcoin offer script, then accept the offer
+    /// in full once the payment got `MIN_PAYMENT_CONFIRMATIONS` more blocks, the last fill
+    /// sweeping what the vault still holds to the maker
+    TakeBtcOffer(TakeBtcOffer),
+    /// Create the fee config of the program, the signer becoming its admin, or update it
+    SetFeeConfig(SetFeeConfig),
+}
+
+/// Makes the offer `params.id`, moving the offered token A from the maker associated token
+/// account to the vault. For native bitcoin offers `token_mint_b` is the system program. The fee
+/// config of the program comes last, its current fee is copied into token B offers.
+pub fn make_offer_ix(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    token_mint_a: &Pubkey,
+    token_mint_b: &Pubkey,
+    params: MakeOffer,
+) -> Instruction {
+    let offer = find_offer_address(program_id, maker, params.id).0;
+    let token_mint_b_meta = if params.btc_script_pubkey.is_some() {
+        AccountMeta::new_readonly(*token_mint_b, false)
+    } else {
+        AccountMeta::new(*token_mint_b, false)
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(offer, false),
+            AccountMeta::new(*token_mint_a, false),
+            token_mint_b_meta,
+            AccountMeta::new(find_associated_token_address(maker, token_mint_a), false),
+            AccountMeta::new(find_vault_address(&offer, token_mint_a), false),
+            AccountMeta::new(*maker, true),
+            AccountMeta::new_readonly(apl_token::id(), false),
+            AccountMeta::new_readonly(Pubkey::system_program(), false),
+            AccountMeta::new_readonly(apl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(find_fee_config_address(program_id).0, false),
+        ],
+        data: borsh::to_vec(&EscrowInstruction::MakeOffer(params)).unwrap(),
+    }
+}
+
+/// Takes `token_a_amount` of `offer` for `taker`, paying token B pro rata from the taker
+/// associated token account. The last fill returns any token A sent to the vault on top of the
+/// offer to the maker associated token account. The fee config of the program comes next, and
+/// offers made with a fee pay it to the fee recipient associated token account.
+pub fn take_offer_ix(
+    program_id: &Pubkey,
+    offer: &Offer,
+    taker: &Pubkey,
+    token_a_amount: u64,
+) -> Instruction {
+    let offer_address = offer.address(program_id);
+
+    let mut instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(offer_address, false),
+            AccountMeta::new(offer.token_mint_a, false),
+            AccountMeta::new(offer.token_mint_b, false),
+            AccountMeta::new(
+                find_associated_token_address(&offer.maker, &offer.token_mint_b),
+                false,
+            ),
+            AccountMeta::new(
+                find_associated_token_address(taker, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new(
+                find_associated_token_address(taker, &offer.token_mint_b),
+                false,
+            ),
+            AccountMeta::new(
+                find_vault_address(&offer_address, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new(offer.maker, false),
+            AccountMeta::new(*taker, true),
+            AccountMeta::new_readonly(apl_token::id(), false),
+            AccountMeta::new_readonly(Pubkey::system_program(), false),
+            AccountMeta::new_readonly(apl_associated_token_account::id(), false),
+            AccountMeta::new(
+                find_associated_token_address(&offer.maker, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new_readonly(find_fee_config_address(program_id).0, false),
+        ],
+        data: borsh::to_vec(&EscrowInstruction::TakeOffer(TakeOffer { token_a_amount })).unwrap(),
+    };
+
+    if let Some(fee) = &offer.fee {
+        instruction.accounts.push(AccountMeta::new(
+            find_associated_token_address(&fee.fee_recipient, &offer.token_mint_b),
+            false,
+        ));
+    }
+
+    instruction
+}
+
+/// Takes all of the native bitcoin `offer` for `taker`, once `payment` pays the offer script. The
+/// first take registers the payment once it is mined, the same instruction sent again once
+/// `MIN_PAYMENT_CONFIRMATIONS` more blocks were mined settles the offer, returning any token A
+/// sent to the vault on top of the offer to the maker associated token account.
+pub fn take_btc_offer_ix(
+    program_id: &Pubkey,
+    offer: &Offer,
+    taker: &Pubkey,
+    payment: UtxoMeta,
+) -> Instruction {
+    let offer_address = offer.address(program_id);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(offer_address, false),
+            AccountMeta::new_readonly(offer.token_mint_a, false),
+            AccountMeta::new(
+                find_associated_token_address(taker, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new(
+                find_vault_address(&offer_address, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new(offer.maker, false),
+            AccountMeta::new(*taker, true),
+            AccountMeta::new_readonly(apl_token::id(), false),
+            AccountMeta::new(
+                find_associated_token_address(&offer.maker, &offer.token_mint_a),
+                false,
+            ),
+        ],
+        data: borsh::to_vec(&EscrowInstruction::TakeBtcOffer(TakeBtcOffer { payment })).unwrap(),
+    }
+}
+
+/// Cancels `offer`, refunding the maker associated token account with the vault content
+pub fn cancel_offer_ix(program_id: &Pubkey, offer: &Offer) -> Instruction {
+    let offer_address = offer.address(program_id);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(offer_address, false),
+            AccountMeta::new_readonly(offer.token_mint_a, false),
+            AccountMeta::new(
+                find_associated_token_address(&offer.maker, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new(
+                find_vault_address(&offer_address, &offer.token_mint_a),
+                false,
+            ),
+            AccountMeta::new(offer.maker, true),
+            AccountMeta::new_readonly(apl_token::id(), false),
+        ],
+        data: borsh::to_vec(&EscrowInstruction::CancelOffer).unwrap(),
+    }
+}
+
+/// Same accounts as `cancel_offer_ix` for an expired `offer`, the maker doesn't have to sign
+pub fn refund_expired_offer_ix(program_id: &Pubkey, offer: &Offer) -> Instruction {
+    let mut instruction = cancel_offer_ix(program_id, offer);
+    instruction.accounts[4].is_signer = false;
+    instruction.data = borsh::to_vec(&EscrowInstruction::RefundExpiredOffer).unwrap();
+    instruction
+}
+
+/// Creates the fee config of the program with `admin` as its admin, `params.fee_config_utxo` being
+/// the UTXO sent to the fee config address, or updates it when `admin` is its admin
+pub fn set_fee_config_ix(program_id: &Pubkey, admin: &Pubkey, params: SetFeeConfig) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(find_fee_config_address(program_id).0, false),
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(Pubkey::system_program(), false),
+        ],
+        data: borsh::to_vec(&EscrowInstruction::SetFeeConfig(params)).unwrap(),
+    }
+}
@@ -0,0 +1,55 @@
+//! Types shared by the escrow program and its clients: the instruction wire format, the offer and
+//! fee config account layouts, the program errors, the address derivations and the instruction
+//! builders, which list the accounts in the order the program reads them.
+use arch_program::{bitcoin::ScriptBuf, pubkey::Pubkey, utxo::UtxoMeta};
+
+pub mod error;
+pub mod instruction;
+pub mod state;
+
+/// First seed of program derived offer addresses
+pub const OFFER_SEED: &[u8] = b"offer";
+
+/// Only seed of the program derived fee config address
+pub const FEE_CONFIG_SEED: &[u8] = b"fee_config";
+
+/// Basis points in 100%, the highest fee
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Bitcoin blocks mined after a payment is registered before it can settle the offer, the payment
+/// having to be mined already when it is registered
+pub const MIN_PAYMENT_CONFIRMATIONS: u64 = 6;
+
+/// Finds the address of the offer `id` made by `maker`
+pub fn find_offer_address(program_id: &Pubkey, maker: &Pubkey, id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OFFER_SEED, maker.as_ref(), &id.to_le_bytes()], program_id)
+}
+
+/// Finds the address of the fee config of the escrow program `program_id`
+pub fn find_fee_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_CONFIG_SEED], program_id)
+}
+
+/// Associated token account of `owner` for `mint`
+pub fn find_associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    apl_associated_token_account::get_associated_token_address_and_bump_seed(
+        owner,
+        mint,
+        &apl_associated_token_account::id(),
+    )
+    .0
+}
+
+/// Finds the vault holding the token A of `offer`, its associated token account
+pub fn find_vault_address(offer: &Pubkey, token_mint_a: &Pubkey) -> Pubkey {
+    find_associated_token_address(offer, token_mint_a)
+}
+
+/// OP_RETURN output script a `TakeBtcOffer` payment transaction has to carry as its only
+/// OP_RETURN output, committing to the anchor UTXO the offer was made with
+pub fn bitcoin_payment_commitment(offer_utxo: &UtxoMeta) -> ScriptBuf {
+    let mut commitment = [0u8; 36];
+    commitment[..32].copy_from_slice(offer_utxo.txid());
+    commitment[32..].copy_from_slice(&offer_utxo.vout().to_le_bytes());
+    ScriptBuf::new_op_return(commitment)
+}
@@ -0,0 +1,275 @@
+use arch_program::{program_error::ProgramError, pubkey::Pubkey, utxo::UtxoMeta};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{find_offer_address, MAX_FEE_BPS};
+
+/// When an offer expires, checked by the program against the chain it runs on
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfferExpiry {
+    /// Expires once the Bitcoin chain reaches this block height, as seen by `get_bitcoin_block_height`
+    BitcoinBlockHeight(u64),
+    /// Expires once the Arch clock reaches this unix timestamp, as seen by `get_clock`
+    UnixTimestamp(i64),
+}
+
+impl OfferExpiry {
+    /// Whether the expiry passed once the Bitcoin chain is at `bitcoin_block_height` and the Arch
+    /// clock at `unix_timestamp`
+    pub fn has_passed(&self, bitcoin_block_height: u64, unix_timestamp: i64) -> bool {
+        match self {
+            OfferExpiry::BitcoinBlockHeight(height) => bitcoin_block_height >= *height,
+            OfferExpiry::UnixTimestamp(timestamp) => unix_timestamp >= *timestamp,
+        }
+    }
+}
+
+/// Offer account layout, at the address derived from its maker and id
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    /// Unique identifier for the offer
+    pub id: u64,
+    /// Public key of the user who created the offer
+    pub maker: Pubkey,
+    /// Public key of the mint for token A
+    pub token_mint_a: Pubkey,
+    /// Public key of the mint for token B, the system program for native bitcoin
+    pub token_mint_b: Pubkey,
+    /// Amount of token B wanted in exchange, in sats when `btc_script_pubkey` is set
+    pub token_b_wanted_amount: u64,
+    /// Script the taker pays native bitcoin to, `None` for token B offers
+    pub btc_script_pubkey: Option<Vec<u8>>,
+    /// Amount of token A initially deposited in the vault
+    pub token_a_offered_amount: u64,
+    /// Amount of token A still in the vault, waiting to be taken
+    pub token_a_remaining_amount: u64,
+    /// Point after which the offer can no longer be taken and can be refunded by anyone
+    pub expiry: Option<OfferExpiry>,
+    /// Fee of the program taken out of the token B paid to the maker, `None` on native bitcoin
+    /// offers and offers made while the fee was zero
+    pub fee: Option<OfferFee>,
+    /// Only taker allowed to take the offer, anyone can when `None`
+    pub allowed_taker: Option<Pubkey>,
+    /// Bitcoin payment registered on a native bitcoin offer, waiting for its confirmations
+    pub btc_payment: Option<PendingBtcPayment>,
+    /// The bump seed for the offer's Program Derived Address
+    pub bump: u8,
+}
+
+impl Offer {
+    /// Decodes the data of an offer account, failing on closed or foreign accounts
+    pub fn decode(data: &[u8]) -> Result<Self, ProgramError> {
+        Offer::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Address of this offer for the escrow program `program_id`
+    pub fn address(&self, program_id: &Pubkey) -> Pubkey {
+        find_offer_address(program_id, &self.maker, self.id).0
+    }
+
+    /// Whether the offer expired once the Bitcoin chain is at `bitcoin_block_height` and the Arch
+    /// clock at `unix_timestamp`
+    pub fn is_expired(&self, bitcoin_block_height: u64, unix_timestamp: i64) -> bool {
+        self.expiry
+            .is_some_and(|expiry| expiry.has_passed(bitcoin_block_height, unix_timestamp))
+    }
+
+    /// Whether `taker` is allowed to take this offer
+    pub fn allows_taker(&self, taker: &Pubkey) -> bool {
+        self.allowed_taker
+            .is_none_or(|allowed_taker| allowed_taker == *taker)
+    }
+
+    /// Token B owed to the maker once `token_a_filled` of the offered token A has been taken,
+    /// rounded up so that partial fills never pay less than the offer price and the last fill
+    /// pays exactly what is left of `token_b_wanted_amount`
+    pub fn token_b_owed(&self, token_a_filled: u64) -> u64 {
+        let owed = (u128::from(token_a_filled) * u128::from(self.token_b_wanted_amount))
+            .div_ceil(u128::from(self.token_a_offered_amount));
+
+        // never above token_b_wanted_amount as token_a_filled <= token_a_offered_amount
+        owed as u64
+    }
+}
+
+/// Bitcoin payment registered by the first `TakeBtcOffer` of a taker, settling the offer on a
+/// later one once `MIN_PAYMENT_CONFIRMATIONS` blocks were mined
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingBtcPayment {
+    /// Output of the taker's Bitcoin transaction paying the offer script
+    pub payment: UtxoMeta,
+    /// Taker who registered the payment, the only one who can settle it
+    pub taker: Pubkey,
+    /// Bitcoin block height when the payment was registered
+    pub registered_at: u64,
+}
+
+/// Fee config of the escrow program, at the single address derived from `FEE_CONFIG_SEED`. Offers are
+/// made with it, and token B offers taken with it.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeeConfig {
+    /// Public key of the admin, the signer who created the config and the only one who can change
+    /// it
+    pub admin: Pubkey,
+    /// Owner of the token B accounts receiving the fee
+    pub fee_recipient: Pubkey,
+    /// Fee in basis points of the token B paid by the taker
+    pub fee_bps: u16,
+    /// The bump seed for the fee config's Program Derived Address
+    pub bump: u8,
+}
+
+impl FeeConfig {
+    /// Decodes the data of a fee config account
+    pub fn decode(data: &[u8]) -> Result<Self, ProgramError> {
+        FeeConfig::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+/// Fee of an offer, copied from the fee config when the offer is made so that raising the fee
+/// doesn't apply to open offers
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfferFee {
+    /// Owner of the token B accounts receiving the fee
+    pub fee_recipient: Pubkey,
+    /// Fee in basis points of the token B paid by the taker
+    pub fee_bps: u16,
+}
+
+impl OfferFee {
+    /// Fee owed once `token_b_paid` of an offer token B has been paid, rounded down in favor of
+    /// the maker. Taken on the cumulated amount paid so that splitting a take in several fills
+    /// doesn't round the fee away: a take pays the difference of the fee owed after and before it.
+    pub fn fee_owed(&self, token_b_paid: u64) -> u64 {
+        let fee = u128::from(token_b_paid) * u128::from(self.fee_bps) / u128::from(MAX_FEE_BPS);
+
+        // never above token_b_paid as fee_bps <= MAX_FEE_BPS
+        fee as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offer_wire_format() {
+        let offer = Offer {
+            id: 1,
+            maker: Pubkey::from_slice(&[1; 32]),
+            token_mint_a: Pubkey::from_slice(&[2; 32]),
+            token_mint_b: Pubkey::from_slice(&[3; 32]),
+            token_b_wanted_amount: 30,
+            btc_script_pubkey: None,
+            token_a_offered_amount: 100,
+            token_a_remaining_amount: 49,
+            expiry: Some(OfferExpiry::BitcoinBlockHeight(200)),
+            fee: Some(OfferFee {
+                fee_recipient: Pubkey::from_slice(&[6; 32]),
+                fee_bps: 250,
+            }),
+            allowed_taker: Some(Pubkey::from_slice(&[5; 32])),
+            btc_payment: None,
+            bump: 255,
+        };
+
+        let mut expected = vec![1, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend([1; 32]);
+        expected.extend([2; 32]);
+        expected.extend([3; 32]);
+        expected.extend([30, 0, 0, 0, 0, 0, 0, 0]);
+        expected.push(0);
+        expected.extend([100, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend([49, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend([1, 0, 200, 0, 0, 0, 0, 0, 0, 0]);
+        expected.push(1);
+        expected.extend([6; 32]);
+        expected.extend([250, 0]);
+        expected.push(1);
+        expected.extend([5; 32]);
+        expected.push(0);
+        expected.push(255);
+
+        assert_eq!(borsh::to_vec(&offer).unwrap(), expected);
+        assert_eq!(Offer::decode(&expected).unwrap(), offer);
+        assert_eq!(Offer::decode(&[]), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn token_b_owed_rounds_up() {
+        let offer = Offer {
+            id: 1,
+            maker: Pubkey::from_slice(&[1; 32]),
+            token_mint_a: Pubkey::from_slice(&[2; 32]),
+            token_mint_b: Pubkey::from_slice(&[3; 32]),
+            token_b_wanted_amount: 30,
+            btc_script_pubkey: None,
+            token_a_offered_amount: 100,
+            token_a_remaining_amount: 100,
+            expiry: None,
+            fee: None,
+            allowed_taker: None,
+            btc_payment: None,
+            bump: 255,
+        };
+
+        assert_eq!(offer.token_b_owed(0), 0);
+        assert_eq!(offer.token_b_owed(1), 1);
+        assert_eq!(offer.token_b_owed(50), 15);
+        assert_eq!(offer.token_b_owed(51), 16);
+        assert_eq!(offer.token_b_owed(100), 30);
+    }
+
+    #[test]
+    fn is_expired_checks_its_chain() {
+        let offer = |expiry| Offer {
+            id: 1,
+            maker: Pubkey::from_slice(&[1; 32]),
+            token_mint_a: Pubkey::from_slice(&[2; 32]),
+            token_mint_b: Pubkey::from_slice(&[3; 32]),
+            token_b_wanted_amount: 30,
+            btc_script_pubkey: None,
+            token_a_offered_amount: 100,
+            token_a_remaining_amount: 100,
+            expiry,
+            fee: None,
+            allowed_taker: None,
+            btc_payment: None,
+            bump: 255,
+        };
+
+        assert!(!offer(None).is_expired(u64::MAX, i64::MAX));
+
+        let by_height = offer(Some(OfferExpiry::BitcoinBlockHeight(200)));
+        assert!(!by_height.is_expired(199, i64::MAX));
+        assert!(by_height.is_expired(200, 0));
+
+        let by_timestamp = offer(Some(OfferExpiry::UnixTimestamp(1_000)));
+        assert!(!by_timestamp.is_expired(u64::MAX, 999));
+        assert!(by_timestamp.is_expired(0, 1_000));
+    }
+
+    #[test]
+    fn fee_owed_rounds_down() {
+        let fee = OfferFee {
+            fee_recipient: Pubkey::from_slice(&[2; 32]),
+            fee_bps: 250,
+        };
+
+        assert_eq!(fee.fee_owed(0), 0);
+        assert_eq!(fee.fee_owed(39), 0);
+        assert_eq!(fee.fee_owed(40), 1);
+        assert_eq!(fee.fee_owed(1_000), 25);
+        assert_eq!(fee.fee_owed(u64::MAX), u64::MAX / 40);
+
+        // two fills of 20 pay the fee of a single fill of 40
+        let first_fill = fee.fee_owed(20) - fee.fee_owed(0);
+        let second_fill = fee.fee_owed(40) - fee.fee_owed(20);
+        assert_eq!(first_fill + second_fill, 1);
+
+        let full_fee = OfferFee {
+            fee_bps: MAX_FEE_BPS,
+            ..fee
+        };
+        assert_eq!(full_fee.fee_owed(u64::MAX), u64::MAX);
+    }
+}
@@ -1,7 +1,11 @@
 use arch_program::{
     account::{AccountInfo},
+    bitcoin::{self, Transaction},
     entrypoint, msg,
-    program::{invoke, invoke_signed, next_account_info},
+    program::{
+        get_bitcoin_block_height, get_bitcoin_tx, get_bitcoin_tx_confirmation, get_clock, invoke,
+        invoke_signed, next_account_info,
+    },
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
@@ -9,45 +13,14 @@ use arch_program::{
     utxo::UtxoMeta,
     rent::minimum_rent,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
-
-#[derive(BorshDeserialize, BorshSerialize, Debug)]
-pub struct MakeOffer {
-    /// The bump seed for the offer's Program Derived Address
-    pub offer_bump_seed: u8,
-    /// The UTXO metadata associated with this offer
-    pub offer_utxo: UtxoMeta,
-    /// Unique identifier for the offer
-    pub id: u64,
-    /// Amount of token A being offered
-    pub token_a_offered_amount: u64,
-    /// Amount of token B wanted in exchange
-    pub token_b_wanted_amount: u64,
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Debug)]
-pub struct Offer {
-    /// Unique identifier for the offer
-    pub id: u64,
-    /// Public key of the user who created the offer
-    pub maker: Pubkey,
-    /// Public key of the mint for token A
-    pub token_mint_a: Pubkey,
-    /// Public key of the mint for token B
-    pub token_mint_b: Pubkey,
-    /// Amount of token B wanted in exchange
-    pub token_b_wanted_amount: u64,
-    /// The bump seed for the offer's Program Derived Address
-    pub bump: u8,
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-enum EscrowInstruction {
-    /// Create a new offer to exchange tokens
-    MakeOffer(MakeOffer),
-    /// Accept an existing offer
-    TakeOffer,
-}
+use borsh::BorshDeserialize;
+use escrow_interface::{
+    bitcoin_payment_commitment,
+    error::EscrowError,
+    instruction::{EscrowInstruction, MakeOffer, SetFeeConfig, TakeBtcOffer, TakeOffer},
+    state::{FeeConfig, Offer, OfferExpiry, OfferFee, PendingBtcPayment},
+    FEE_CONFIG_SEED, MAX_FEE_BPS, MIN_PAYMENT_CONFIRMATIONS, OFFER_SEED,
+};
 
 // Program entrypoint
 entrypoint!(process_instruction);
@@ -61,7 +34,11 @@ fn process_instruction(
 
     match instruction {
         EscrowInstruction::MakeOffer(data) => process_make_offer(program_id, accounts, data),
-        EscrowInstruction::TakeOffer => process_take_offer(program_id, accounts, instruction_data),
+        EscrowInstruction::TakeOffer(data) => process_take_offer(program_id, accounts, data),
+        EscrowInstruction::CancelOffer => process_cancel_offer(program_id, accounts, false),
+        EscrowInstruction::RefundExpiredOffer => process_cancel_offer(program_id, accounts, true),
+        EscrowInstruction::TakeBtcOffer(data) => process_take_btc_offer(program_id, accounts, data),
+        EscrowInstruction::SetFeeConfig(data) => process_set_fee_config(program_id, accounts, data),
     }
 }
 
@@ -80,43 +57,83 @@ fn process_make_offer(
     let token_program = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
     let associated_token_program = next_account_info(account_info_iter)?;
+    let fee_config_info = next_account_info(account_info_iter)?;
 
     // account validations
-    assert!(maker.is_writable);
-    assert!(maker.is_signer);
+    check_writable(maker)?;
+    check_signer(maker)?;
 
-    assert!(offer_info.is_writable);
-    assert!(offer_info.data_is_empty());
+    check_writable(offer_info)?;
+    if !offer_info.data_is_empty() {
+        return Err(EscrowError::OfferAlreadyInitialized.into());
+    }
+
+    check_writable(token_mint_a)?;
+    check_owner(token_mint_a, &apl_token::id())?;
 
-    assert!(token_mint_a.is_writable);
-    assert_eq!(token_mint_a.owner, &apl_token::id());
+    if data.btc_script_pubkey.is_some() {
+        check_program(token_mint_b, &Pubkey::system_program())?;
+    } else {
+        check_writable(token_mint_b)?;
+        check_owner(token_mint_b, &apl_token::id())?;
+    }
 
-    assert!(token_mint_b.is_writable);
-    assert_eq!(token_mint_b.owner, &apl_token::id());
+    check_writable(maker_token_account_a)?;
+    check_owner(maker_token_account_a, &apl_token::id())?;
 
-    assert_eq!(token_program.key, &apl_token::id());
-    assert_eq!(
-        associated_token_program.key,
-        &apl_associated_token_account::id()
-    );
-    assert_eq!(system_program.key, &Pubkey::system_program());
+    check_writable(vault)?;
+    check_vault(vault, offer_info.key, token_mint_a.key)?;
+
+    check_program(token_program, &apl_token::id())?;
+    check_program(
+        associated_token_program,
+        &apl_associated_token_account::id(),
+    )?;
+    check_program(system_program, &Pubkey::system_program())?;
 
     // get params
     let params: MakeOffer = data;
     let id = params.id.to_le_bytes();
 
+    if params.token_a_offered_amount == 0 {
+        return Err(EscrowError::InvalidAmount.into());
+    }
+
+    if has_passed(params.expiry) {
+        return Err(EscrowError::OfferExpired.into());
+    }
+
+    // the fee is copied into the offer so that the admin can't raise it once the offer is open.
+    // Native bitcoin is paid straight to the maker script, out of reach of the fee
+    let fee_config = read_fee_config(program_id, fee_config_info)?;
+    let fee = (fee_config.fee_bps > 0 && params.btc_script_pubkey.is_none()).then_some(OfferFee {
+        fee_recipient: fee_config.fee_recipient,
+        fee_bps: fee_config.fee_bps,
+    });
+
     // offer PDA seeds
-    let offer_seeds = &[b"offer", maker.key.as_ref(), id.as_ref()];
+    let offer_seeds = &[OFFER_SEED, maker.key.as_ref(), id.as_ref()];
 
     // verify the program address is correct
     let expected_offer_pda = Pubkey::find_program_address(offer_seeds, program_id);
-    assert_eq!(offer_info.key, &expected_offer_pda.0);
+    // a bump other than the derived one would make every later offer signature fail, locking the
+    // vault
+    if offer_info.key != &expected_offer_pda.0 || params.offer_bump_seed != expected_offer_pda.1 {
+        return Err(EscrowError::InvalidOfferAddress.into());
+    }
 
     let offer_data = Offer {
-        bump: params.offer_bump_seed,
+        bump: expected_offer_pda.1,
         maker: *maker.key,
         id: params.id,
         token_b_wanted_amount: params.token_b_wanted_amount,
+        token_a_offered_amount: params.token_a_offered_amount,
+        token_a_remaining_amount: params.token_a_offered_amount,
+        expiry: params.expiry,
+        fee,
+        allowed_taker: params.allowed_taker,
+        btc_payment: None,
+        btc_script_pubkey: params.btc_script_pubkey,
         token_mint_a: *token_mint_a.key,
         token_mint_b: *token_mint_b.key,
     };
@@ -124,7 +141,7 @@ fn process_make_offer(
         borsh::to_vec(&offer_data).map_err(|_| ProgramError::InvalidAccountData)?;
 
     let offer_signer_seeds = &[
-        b"offer",
+        OFFER_SEED,
         maker.key.as_ref(),
         id.as_ref(),
         &[expected_offer_pda.1],
@@ -149,26 +166,13 @@ fn process_make_offer(
         &[offer_signer_seeds],
     )?;
 
-    assert!(maker_token_account_a.is_writable);
-    assert_eq!(maker_token_account_a.owner, &apl_token::id());
-
-    let associated_account_address =
-        apl_associated_token_account::get_associated_token_address_and_bump_seed(
-            &offer_info.key,
-            &token_mint_a.key,
-            &apl_associated_token_account::id(),
-        )
-        .0;
-    assert!(vault.is_writable);
-    assert_eq!(associated_account_address, *vault.key);
-
     // build transfer instruction
     let transfer_ix = apl_token::instruction::transfer(
         token_program.key,
         maker_token_account_a.key,
         vault.key,
         maker.key,
-        &[&maker.key],
+        &[maker.key],
         params.token_a_offered_amount,
     )?;
 
@@ -205,7 +209,7 @@ fn process_make_offer(
 fn process_take_offer(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _instruction_data: &[u8],
+    data: TakeOffer,
 ) -> Result<(), ProgramError> {
     let account_info_iter = &mut accounts.iter();
     let offer_info = next_account_info(account_info_iter)?;
@@ -220,49 +224,126 @@ fn process_take_offer(
     let token_program = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
     let associated_token_program = next_account_info(account_info_iter)?;
+    let maker_token_account_a = next_account_info(account_info_iter)?;
+    let fee_config_info = next_account_info(account_info_iter)?;
 
     // account validation
-    assert!(maker.is_writable);
+    check_writable(maker)?;
 
-    assert!(taker.is_writable);
-    assert!(taker.is_signer);
+    check_writable(taker)?;
+    check_signer(taker)?;
 
-    assert!(token_mint_a.is_writable);
-    assert_eq!(token_mint_a.owner, &apl_token::id());
+    check_writable(token_mint_a)?;
+    check_owner(token_mint_a, &apl_token::id())?;
 
-    assert!(token_mint_b.is_writable);
-    assert_eq!(token_mint_b.owner, &apl_token::id());
+    check_writable(token_mint_b)?;
+    check_owner(token_mint_b, &apl_token::id())?;
 
-    assert!(maker_token_account_b.is_writable);
+    check_writable(maker_token_account_b)?;
+    check_owner(maker_token_account_b, &apl_token::id())?;
 
-    assert!(taker_token_account_a.is_writable);
-    assert_eq!(taker_token_account_a.owner, &apl_token::id());
+    check_writable(taker_token_account_a)?;
+    check_owner(taker_token_account_a, &apl_token::id())?;
 
-    assert!(taker_token_account_b.is_writable);
-    assert_eq!(taker_token_account_b.owner, &apl_token::id());
+    check_writable(taker_token_account_b)?;
+    check_owner(taker_token_account_b, &apl_token::id())?;
 
-    assert_eq!(vault.owner, &apl_token::id());
+    check_writable(vault)?;
+    check_owner(vault, &apl_token::id())?;
+    check_vault(vault, offer_info.key, token_mint_a.key)?;
 
-    assert_eq!(
-        associated_token_program.key,
-        &apl_associated_token_account::id()
-    );
-    assert_eq!(system_program.key, &Pubkey::system_program());
+    check_program(token_program, &apl_token::id())?;
+    check_program(
+        associated_token_program,
+        &apl_associated_token_account::id(),
+    )?;
+    check_program(system_program, &Pubkey::system_program())?;
+
+    check_writable(offer_info)?;
+    check_owner(offer_info, program_id)?;
 
-    let offer = Offer::try_from_slice(&offer_info.data.borrow()[..])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
-    assert_eq!(&offer.maker, maker.key);
-    assert_eq!(&offer.token_mint_a, token_mint_a.key);
-    assert_eq!(&offer.token_mint_b, token_mint_b.key);
+    let mut offer = Offer::decode(&offer_info.data.borrow())?;
+    if &offer.token_mint_a != token_mint_a.key
+        || &offer.token_mint_b != token_mint_b.key
+        || offer.btc_script_pubkey.is_some()
+    {
+        return Err(EscrowError::InvalidMint.into());
+    }
 
-    let offer_signer = &[b"offer", maker.key.as_ref(), &offer.id.to_le_bytes()];
+    let offer_signer = &[OFFER_SEED, maker.key.as_ref(), &offer.id.to_le_bytes()];
 
     let offer_key = Pubkey::find_program_address(offer_signer, program_id).0;
-    assert_eq!(*offer_info.key, offer_key);
+    if &offer.maker != maker.key || *offer_info.key != offer_key {
+        return Err(EscrowError::InvalidOfferAddress.into());
+    }
+
+    // token B is paid to the maker, and the taker pays and gets paid with the offer mints
+    check_token_account(
+        maker_token_account_b,
+        &offer.token_mint_b,
+        Some(&offer.maker),
+    )?;
+    check_token_account(taker_token_account_a, &offer.token_mint_a, None)?;
+    check_token_account(taker_token_account_b, &offer.token_mint_b, None)?;
+
+    if has_passed(offer.expiry) {
+        return Err(EscrowError::OfferExpired.into());
+    }
 
-    let vault_amount_a = apl_token::state::Account::unpack(&vault.data.borrow())?.amount;
-    let taker_amount_a_before_transfer =
-        apl_token::state::Account::unpack(&taker_token_account_a.data.borrow())?.amount;
+    check_taker(&offer, taker.key)?;
+
+    // offers made with a fee are taken with the token B account of the fee recipient, at the
+    // current fee if the admin lowered it since
+    let fee_config = read_fee_config(program_id, fee_config_info)?;
+    let fee = match &offer.fee {
+        Some(offer_fee) => {
+            let fee_token_account_b = next_account_info(account_info_iter)?;
+
+            check_writable(fee_token_account_b)?;
+            check_owner(fee_token_account_b, &apl_token::id())?;
+            let fee_account =
+                apl_token::state::Account::unpack(&fee_token_account_b.data.borrow())?;
+            if fee_account.mint != offer.token_mint_b
+                || fee_account.owner != offer_fee.fee_recipient
+                || fee_token_account_b.key == maker_token_account_b.key
+                || fee_token_account_b.key == taker_token_account_b.key
+            {
+                return Err(EscrowError::InvalidFeeAccount.into());
+            }
+
+            let offer_fee = OfferFee {
+                fee_recipient: offer_fee.fee_recipient,
+                fee_bps: offer_fee.fee_bps.min(fee_config.fee_bps),
+            };
+
+            Some((offer_fee, fee_token_account_b))
+        }
+        None => None,
+    };
+
+    let fill_amount = data.token_a_amount;
+    if fill_amount == 0 || fill_amount > offer.token_a_remaining_amount {
+        return Err(EscrowError::InvalidAmount.into());
+    }
+
+    let token_a_filled_before = offer.token_a_offered_amount - offer.token_a_remaining_amount;
+    let token_b_paid_before = offer.token_b_owed(token_a_filled_before);
+    let token_b_paid_after = offer.token_b_owed(token_a_filled_before + fill_amount);
+    let token_b_amount = token_b_paid_after - token_b_paid_before;
+
+    let fee_amount = fee.as_ref().map_or(0, |(offer_fee, _)| {
+        offer_fee.fee_owed(token_b_paid_after) - offer_fee.fee_owed(token_b_paid_before)
+    });
+    let maker_amount_b = token_b_amount - fee_amount;
+
+    let maker_amount_b_before_transfer = token_amount(maker_token_account_b)?;
+    let taker_amount_a_before_transfer = token_amount(taker_token_account_a)?;
+    let taker_amount_b_before_transfer = token_amount(taker_token_account_b)?;
+    let vault_amount_a_before_transfer = token_amount(vault)?;
+    let fee_amount_b_before_transfer = fee
+        .as_ref()
+        .map(|(_, fee_token_account_b)| token_amount(fee_token_account_b))
+        .transpose()?;
 
     invoke(
         &apl_token::instruction::transfer(
@@ -270,8 +351,8 @@ fn process_take_offer(
             taker_token_account_b.key,
             maker_token_account_b.key,
             taker.key,
-            &[&taker.key],
-            offer.token_b_wanted_amount,
+            &[taker.key],
+            maker_amount_b,
         )?,
         &[
             taker_token_account_b.clone(),
@@ -281,8 +362,29 @@ fn process_take_offer(
         ],
     )?;
 
+    if let Some((_, fee_token_account_b)) = &fee {
+        if fee_amount > 0 {
+            invoke(
+                &apl_token::instruction::transfer(
+                    token_program.key,
+                    taker_token_account_b.key,
+                    fee_token_account_b.key,
+                    taker.key,
+                    &[taker.key],
+                    fee_amount,
+                )?,
+                &[
+                    taker_token_account_b.clone(),
+                    (*fee_token_account_b).clone(),
+                    taker.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+    }
+
     let offer_signer_seeds = &[
-        b"offer",
+        OFFER_SEED,
         maker.key.as_ref(),
         &offer.id.to_le_bytes(),
         &[offer.bump],
@@ -295,7 +397,7 @@ fn process_take_offer(
             taker_token_account_a.key,
             offer_info.key,
             &[offer_info.key, taker.key],
-            vault_amount_a,
+            fill_amount,
         )?,
         &[
             token_mint_a.clone(),
@@ -308,33 +410,703 @@ fn process_take_offer(
         &[offer_signer_seeds],
     )?;
 
-    let taker_amount_a =
-        apl_token::state::Account::unpack(&taker_token_account_a.data.borrow())?.amount;
-    let maker_amount_b =
-        apl_token::state::Account::unpack(&maker_token_account_b.data.borrow())?.amount;
+    // token B went from the taker to the maker and the fee recipient, and token A from the vault
+    // to the taker
+    check_token_amount(
+        maker_token_account_b,
+        maker_amount_b_before_transfer.checked_add(maker_amount_b),
+    )?;
+    if let Some((_, fee_token_account_b)) = &fee {
+        check_token_amount(
+            fee_token_account_b,
+            fee_amount_b_before_transfer.and_then(|amount| amount.checked_add(fee_amount)),
+        )?;
+    }
+    check_token_amount(
+        taker_token_account_b,
+        taker_amount_b_before_transfer.checked_sub(token_b_amount),
+    )?;
+    check_token_amount(
+        taker_token_account_a,
+        taker_amount_a_before_transfer.checked_add(fill_amount),
+    )?;
+    check_token_amount(
+        vault,
+        vault_amount_a_before_transfer.checked_sub(fill_amount),
+    )?;
+
+    offer.token_a_remaining_amount -= fill_amount;
+
+    // partially filled, the offer stays open with what is left in the vault
+    if offer.token_a_remaining_amount > 0 {
+        let serialized_offer_data =
+            borsh::to_vec(&offer).map_err(|_| ProgramError::InvalidAccountData)?;
+
+        offer_info
+            .data
+            .try_borrow_mut()
+            .map_err(|_e| ProgramError::AccountBorrowFailed)?
+            .copy_from_slice(&serialized_offer_data);
 
-    assert_eq!(
-        taker_amount_a,
-        taker_amount_a_before_transfer + vault_amount_a
-    );
-    assert_eq!(
-        maker_amount_b,
-        taker_amount_a_before_transfer + offer.token_b_wanted_amount
-    );
+        return Ok(());
+    }
+
+    sweep_vault(
+        &offer,
+        offer_info,
+        vault,
+        maker_token_account_a,
+        token_program,
+        offer_signer_seeds,
+    )?;
 
     invoke_signed(
         &apl_token::instruction::close_account(
             token_program.key,
             vault.key,
-            taker.key,
+            maker.key,
+            offer_info.key,
+            &[],
+        )?,
+        &[vault.clone(), maker.clone(), offer_info.clone()],
+        &[offer_signer_seeds],
+    )?;
+
+    close_offer_account(offer_info, maker)?;
+
+    Ok(())
+}
+
+/// Takes all the token A left in a native bitcoin offer, once the taker's Bitcoin transaction
+/// pays the offer script and is deep enough in the chain
+fn process_take_btc_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: TakeBtcOffer,
+) -> Result<(), ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let offer_info = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let taker_token_account_a = next_account_info(account_info_iter)?;
+    let vault = next_account_info(account_info_iter)?;
+    let maker = next_account_info(account_info_iter)?;
+    let taker = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let maker_token_account_a = next_account_info(account_info_iter)?;
+
+    // account validation
+    check_writable(maker)?;
+
+    check_writable(taker)?;
+    check_signer(taker)?;
+
+    check_writable(offer_info)?;
+    check_owner(offer_info, program_id)?;
+
+    check_owner(token_mint_a, &apl_token::id())?;
+
+    check_writable(taker_token_account_a)?;
+    check_owner(taker_token_account_a, &apl_token::id())?;
+
+    check_writable(vault)?;
+    check_owner(vault, &apl_token::id())?;
+    check_vault(vault, offer_info.key, token_mint_a.key)?;
+
+    check_program(token_program, &apl_token::id())?;
+
+    let mut offer = Offer::decode(&offer_info.data.borrow())?;
+    if &offer.token_mint_a != token_mint_a.key {
+        return Err(EscrowError::InvalidMint.into());
+    }
+    let Some(btc_script_pubkey) = &offer.btc_script_pubkey else {
+        return Err(EscrowError::InvalidMint.into());
+    };
+
+    let offer_signer = &[OFFER_SEED, maker.key.as_ref(), &offer.id.to_le_bytes()];
+
+    let offer_key = Pubkey::find_program_address(offer_signer, program_id).0;
+    if &offer.maker != maker.key || *offer_info.key != offer_key {
+        return Err(EscrowError::InvalidOfferAddress.into());
+    }
+
+    check_taker(&offer, taker.key)?;
+
+    // the payment has to be mined to be registered and still be when settling, the blocks mined
+    // in between being its confirmations
+    check_bitcoin_payment_confirmed(&data.payment)?;
+    check_bitcoin_payment(
+        &data.payment,
+        offer_info.utxo,
+        btc_script_pubkey,
+        offer.token_b_wanted_amount,
+    )?;
+
+    // the first take registers the payment, the taker settles the offer with it once it is deep
+    // enough in the chain for a reorg or a double spend to be unlikely, even past the expiry
+    let Some(btc_payment) = &offer.btc_payment else {
+        if has_passed(offer.expiry) {
+            return Err(EscrowError::OfferExpired.into());
+        }
+
+        offer.btc_payment = Some(PendingBtcPayment {
+            payment: data.payment,
+            taker: *taker.key,
+            registered_at: get_bitcoin_block_height(),
+        });
+
+        let serialized_offer_data =
+            borsh::to_vec(&offer).map_err(|_| ProgramError::InvalidAccountData)?;
+
+        if serialized_offer_data.len() > offer_info.data_len() {
+            offer_info.realloc(serialized_offer_data.len(), true)?;
+        }
+
+        offer_info
+            .data
+            .try_borrow_mut()
+            .map_err(|_e| ProgramError::AccountBorrowFailed)?
+            .copy_from_slice(&serialized_offer_data);
+
+        return Ok(());
+    };
+
+    if btc_payment.payment != data.payment || btc_payment.taker != *taker.key {
+        msg!("offer {} is paid by {:?}", offer.id, btc_payment);
+        return Err(EscrowError::InvalidBitcoinPayment.into());
+    }
+
+    let confirmed_at = btc_payment
+        .registered_at
+        .saturating_add(MIN_PAYMENT_CONFIRMATIONS);
+    if get_bitcoin_block_height() < confirmed_at {
+        return Err(EscrowError::BitcoinPaymentPending.into());
+    }
+
+    let fill_amount = offer.token_a_remaining_amount;
+
+    let taker_amount_a_before_transfer = token_amount(taker_token_account_a)?;
+    let vault_amount_a_before_transfer = token_amount(vault)?;
+
+    let offer_signer_seeds = &[
+        OFFER_SEED,
+        maker.key.as_ref(),
+        &offer.id.to_le_bytes(),
+        &[offer.bump],
+    ];
+
+    invoke_signed(
+        &apl_token::instruction::transfer(
+            token_program.key,
+            vault.key,
+            taker_token_account_a.key,
+            offer_info.key,
+            &[offer_info.key],
+            fill_amount,
+        )?,
+        &[
+            vault.clone(),
+            taker_token_account_a.clone(),
+            offer_info.clone(),
+            token_program.clone(),
+        ],
+        &[offer_signer_seeds],
+    )?;
+
+    check_token_amount(
+        taker_token_account_a,
+        taker_amount_a_before_transfer.checked_add(fill_amount),
+    )?;
+    check_token_amount(
+        vault,
+        vault_amount_a_before_transfer.checked_sub(fill_amount),
+    )?;
+
+    sweep_vault(
+        &offer,
+        offer_info,
+        vault,
+        maker_token_account_a,
+        token_program,
+        offer_signer_seeds,
+    )?;
+
+    invoke_signed(
+        &apl_token::instruction::close_account(
+            token_program.key,
+            vault.key,
+            maker.key,
+            offer_info.key,
+            &[],
+        )?,
+        &[vault.clone(), maker.clone(), offer_info.clone()],
+        &[offer_signer_seeds],
+    )?;
+
+    close_offer_account(offer_info, maker)?;
+
+    Ok(())
+}
+
+/// Creates the fee config of the program on the first call, with its anchor UTXO and the signer
+/// as its admin, and updates it on the next ones signed by that admin
+fn process_set_fee_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: SetFeeConfig,
+) -> Result<(), ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let fee_config_info = next_account_info(account_info_iter)?;
+    let admin = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // account validation
+    check_writable(admin)?;
+    check_signer(admin)?;
+
+    check_writable(fee_config_info)?;
+
+    check_program(system_program, &Pubkey::system_program())?;
+
+    if data.fee_bps > MAX_FEE_BPS {
+        return Err(EscrowError::InvalidFeeConfig.into());
+    }
+
+    // a single fee config per program, whoever creates it stays its admin
+    let (fee_config_key, bump) = Pubkey::find_program_address(&[FEE_CONFIG_SEED], program_id);
+    if *fee_config_info.key != fee_config_key {
+        return Err(EscrowError::InvalidFeeConfig.into());
+    }
+
+    let fee_config = FeeConfig {
+        admin: *admin.key,
+        fee_recipient: data.fee_recipient,
+        fee_bps: data.fee_bps,
+        bump,
+    };
+    let serialized_fee_config =
+        borsh::to_vec(&fee_config).map_err(|_| ProgramError::InvalidAccountData)?;
+
+    if fee_config_info.data_is_empty() {
+        let fee_config_utxo = data
+            .fee_config_utxo
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        invoke_signed(
+            &create_account_with_anchor(
+                admin.key,
+                fee_config_info.key,
+                minimum_rent(serialized_fee_config.len()),
+                serialized_fee_config.len() as u64,
+                program_id,
+                fee_config_utxo
+                    .txid()
+                    .try_into()
+                    .map_err(|_| ProgramError::InvalidInstructionData)?,
+                fee_config_utxo.vout(),
+            ),
+            &[fee_config_info.clone(), admin.clone()],
+            &[&[FEE_CONFIG_SEED, &[bump]]],
+        )?;
+
+        if fee_config_info.data_len() < serialized_fee_config.len() {
+            fee_config_info.realloc(serialized_fee_config.len(), true)?;
+        }
+    } else if read_fee_config(program_id, fee_config_info)?.admin != *admin.key {
+        return Err(EscrowError::InvalidFeeAdmin.into());
+    }
+
+    fee_config_info
+        .data
+        .try_borrow_mut()
+        .map_err(|_e| ProgramError::AccountBorrowFailed)?
+        .copy_from_slice(&serialized_fee_config);
+
+    Ok(())
+}
+
+/// Refunds the maker with the vault content and clears the offer, on the maker request or, with
+/// `expired_refund`, on anyone's once the offer expired
+fn process_cancel_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    expired_refund: bool,
+) -> Result<(), ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let offer_info = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let maker_token_account_a = next_account_info(account_info_iter)?;
+    let vault = next_account_info(account_info_iter)?;
+    let maker = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    // account validation
+    check_writable(maker)?;
+    if !expired_refund {
+        check_signer(maker)?;
+    }
+
+    check_writable(offer_info)?;
+    check_owner(offer_info, program_id)?;
+
+    check_owner(token_mint_a, &apl_token::id())?;
+
+    check_writable(maker_token_account_a)?;
+    check_owner(maker_token_account_a, &apl_token::id())?;
+
+    check_writable(vault)?;
+    check_owner(vault, &apl_token::id())?;
+    check_vault(vault, offer_info.key, token_mint_a.key)?;
+
+    check_program(token_program, &apl_token::id())?;
+
+    let offer = Offer::decode(&offer_info.data.borrow())?;
+    if &offer.token_mint_a != token_mint_a.key {
+        return Err(EscrowError::InvalidMint.into());
+    }
+
+    let offer_signer = &[OFFER_SEED, maker.key.as_ref(), &offer.id.to_le_bytes()];
+
+    let offer_key = Pubkey::find_program_address(offer_signer, program_id).0;
+    if &offer.maker != maker.key || *offer_info.key != offer_key {
+        return Err(EscrowError::InvalidOfferAddress.into());
+    }
+
+    if expired_refund && !has_passed(offer.expiry) {
+        return Err(EscrowError::OfferNotExpired.into());
+    }
+
+    // the taker may already have paid the maker, the offer is only cleared if the registered
+    // payment left the chain
+    if let (Some(btc_payment), Some(btc_script_pubkey)) =
+        (&offer.btc_payment, &offer.btc_script_pubkey)
+    {
+        if check_bitcoin_payment(
+            &btc_payment.payment,
+            offer_info.utxo,
+            btc_script_pubkey,
+            offer.token_b_wanted_amount,
+        )
+        .is_ok()
+        {
+            return Err(EscrowError::BitcoinPaymentPending.into());
+        }
+    }
+
+    check_token_account(
+        maker_token_account_a,
+        &offer.token_mint_a,
+        Some(&offer.maker),
+    )?;
+
+    let vault_amount_a = token_amount(vault)?;
+
+    let offer_signer_seeds = &[
+        OFFER_SEED,
+        maker.key.as_ref(),
+        &offer.id.to_le_bytes(),
+        &[offer.bump],
+    ];
+
+    invoke_signed(
+        &apl_token::instruction::transfer(
+            token_program.key,
+            vault.key,
+            maker_token_account_a.key,
+            offer_info.key,
+            &[offer_info.key],
+            vault_amount_a,
+        )?,
+        &[
+            vault.clone(),
+            maker_token_account_a.clone(),
+            offer_info.clone(),
+            token_program.clone(),
+        ],
+        &[offer_signer_seeds],
+    )?;
+
+    invoke_signed(
+        &apl_token::instruction::close_account(
+            token_program.key,
+            vault.key,
+            maker.key,
             offer_info.key,
             &[],
         )?,
-        &[vault.clone(), taker.clone(), offer_info.clone()],
+        &[vault.clone(), maker.clone(), offer_info.clone()],
         &[offer_signer_seeds],
     )?;
 
+    close_offer_account(offer_info, maker)?;
+
+    Ok(())
+}
+
+/// Whether `expiry` passed on the chain the program runs on, never for offers without one
+fn has_passed(expiry: Option<OfferExpiry>) -> bool {
+    expiry.is_some_and(|expiry| {
+        expiry.has_passed(get_bitcoin_block_height(), get_clock().unix_timestamp)
+    })
+}
+
+/// Checks that `taker` is allowed to take `offer`
+fn check_taker(offer: &Offer, taker: &Pubkey) -> Result<(), ProgramError> {
+    if !offer.allows_taker(taker) {
+        msg!(
+            "offer {} can only be taken by {:?}",
+            offer.id,
+            offer.allowed_taker
+        );
+        return Err(EscrowError::TakerNotAllowed.into());
+    }
+
+    Ok(())
+}
+
+fn check_writable(account: &AccountInfo) -> Result<(), ProgramError> {
+    if !account.is_writable {
+        msg!("account {:?} is not writable", account.key);
+        return Err(EscrowError::AccountNotWritable.into());
+    }
+
+    Ok(())
+}
+
+fn check_signer(account: &AccountInfo) -> Result<(), ProgramError> {
+    if !account.is_signer {
+        msg!("account {:?} did not sign", account.key);
+        return Err(EscrowError::MissingSigner.into());
+    }
+
+    Ok(())
+}
+
+fn check_owner(account: &AccountInfo, owner: &Pubkey) -> Result<(), ProgramError> {
+    if account.owner != owner {
+        msg!("account {:?} is owned by {:?}", account.key, account.owner);
+        return Err(EscrowError::InvalidOwner.into());
+    }
+
+    Ok(())
+}
+
+fn check_program(account: &AccountInfo, program_id: &Pubkey) -> Result<(), ProgramError> {
+    if account.key != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    Ok(())
+}
+
+/// Checks that `vault` is the associated token account of the offer for token A
+fn check_vault(
+    vault: &AccountInfo,
+    offer: &Pubkey,
+    token_mint_a: &Pubkey,
+) -> Result<(), ProgramError> {
+    let associated_account_address =
+        apl_associated_token_account::get_associated_token_address_and_bump_seed(
+            offer,
+            token_mint_a,
+            &apl_associated_token_account::id(),
+        )
+        .0;
+
+    if associated_account_address != *vault.key {
+        return Err(EscrowError::InvalidVault.into());
+    }
+
+    Ok(())
+}
+
+/// Checks that the Bitcoin transaction of `payment` pays at least `sats` to `script_pubkey` with
+/// that output, and commits to the offer anchor `offer_utxo` in its only OP_RETURN output. The
+/// anchor changes every time an offer is made, unlike its address, so a payment can't be reused
+/// for another offer, even one made again with the same id, and a transaction can't pay several
+/// offers to the same script with the same output
+fn check_bitcoin_payment(
+    payment: &UtxoMeta,
+    offer_utxo: &UtxoMeta,
+    script_pubkey: &[u8],
+    sats: u64,
+) -> Result<(), ProgramError> {
+    let txid: [u8; 32] = payment
+        .txid()
+        .try_into()
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let tx: Transaction = get_bitcoin_tx(txid)
+        .and_then(|serialized_tx| bitcoin::consensus::deserialize(&serialized_tx).ok())
+        .ok_or(EscrowError::InvalidBitcoinPayment)?;
+
+    let pays_offer = tx
+        .output
+        .get(payment.vout() as usize)
+        .is_some_and(|output| {
+            output.script_pubkey.as_bytes() == script_pubkey && output.value.to_sat() >= sats
+        });
+
+    let commitment_script = bitcoin_payment_commitment(offer_utxo);
+    let mut op_returns = tx
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey.is_op_return());
+    let commits_to_offer = op_returns
+        .next()
+        .is_some_and(|output| output.script_pubkey == commitment_script)
+        && op_returns.next().is_none();
+
+    if !pays_offer || !commits_to_offer {
+        msg!(
+            "bitcoin payment {:?} pays the offer: {}, commits to the offer: {}",
+            payment,
+            pays_offer,
+            commits_to_offer
+        );
+        return Err(EscrowError::InvalidBitcoinPayment.into());
+    }
+
+    Ok(())
+}
+
+/// Checks that the Bitcoin transaction of `payment` is mined, failing for unknown transactions
+/// and transactions still in the mempool, which can be replaced before they confirm
+fn check_bitcoin_payment_confirmed(payment: &UtxoMeta) -> Result<(), ProgramError> {
+    let txid: [u8; 32] = payment
+        .txid()
+        .try_into()
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    if !get_bitcoin_tx_confirmation(txid) {
+        msg!("bitcoin payment {:?} is not mined", payment);
+        return Err(EscrowError::BitcoinPaymentUnconfirmed.into());
+    }
+
+    Ok(())
+}
+
+/// Reads the fee config at `fee_config_info`, checking it is the one of the program and its fee is
+/// in range
+fn read_fee_config(
+    program_id: &Pubkey,
+    fee_config_info: &AccountInfo,
+) -> Result<FeeConfig, ProgramError> {
+    check_owner(fee_config_info, program_id)?;
+
+    let fee_config = FeeConfig::decode(&fee_config_info.data.borrow())
+        .map_err(|_| EscrowError::InvalidFeeConfig)?;
+
+    let fee_config_key =
+        Pubkey::create_program_address(&[FEE_CONFIG_SEED, &[fee_config.bump]], program_id)?;
+    if *fee_config_info.key != fee_config_key || fee_config.fee_bps > MAX_FEE_BPS {
+        return Err(EscrowError::InvalidFeeConfig.into());
+    }
+
+    Ok(fee_config)
+}
+
+/// Sends what `vault` still holds once `offer` is fully taken back to the maker token A account,
+/// anyone being able to send token A to the vault, so that the vault can be closed
+fn sweep_vault<'a>(
+    offer: &Offer,
+    offer_info: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    maker_token_account_a: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    offer_signer_seeds: &[&[u8]],
+) -> Result<(), ProgramError> {
+    let excess_amount_a = token_amount(vault)?;
+    if excess_amount_a == 0 {
+        return Ok(());
+    }
+
+    check_writable(maker_token_account_a)?;
+    check_owner(maker_token_account_a, &apl_token::id())?;
+    check_token_account(
+        maker_token_account_a,
+        &offer.token_mint_a,
+        Some(&offer.maker),
+    )?;
+
+    invoke_signed(
+        &apl_token::instruction::transfer(
+            token_program.key,
+            vault.key,
+            maker_token_account_a.key,
+            offer_info.key,
+            &[offer_info.key],
+            excess_amount_a,
+        )?,
+        &[
+            vault.clone(),
+            maker_token_account_a.clone(),
+            offer_info.clone(),
+            token_program.clone(),
+        ],
+        &[offer_signer_seeds],
+    )?;
+
+    check_token_amount(vault, Some(0))
+}
+
+/// Moves all the lamports of the offer account to the maker who paid its rent, empties its data
+/// and hands it back to the system program
+fn close_offer_account(offer_info: &AccountInfo, maker: &AccountInfo) -> Result<(), ProgramError> {
+    let lamports = offer_info.lamports();
+
+    let maker_lamports = maker
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    **maker.try_borrow_mut_lamports()? = maker_lamports;
+    **offer_info.try_borrow_mut_lamports()? = 0;
+
     offer_info.realloc(0, true)?;
+    offer_info.assign(&Pubkey::system_program());
+
+    Ok(())
+}
+
+/// Checks that `token_account` holds `mint` tokens and, when set, belongs to `owner`
+fn check_token_account(
+    token_account: &AccountInfo,
+    mint: &Pubkey,
+    owner: Option<&Pubkey>,
+) -> Result<(), ProgramError> {
+    let account = apl_token::state::Account::unpack(&token_account.data.borrow())?;
+    if account.mint != *mint || owner.is_some_and(|owner| account.owner != *owner) {
+        msg!(
+            "token account {:?} holds {:?} tokens for {:?}",
+            token_account.key,
+            account.mint,
+            account.owner
+        );
+        return Err(EscrowError::InvalidTokenAccount.into());
+    }
+
+    Ok(())
+}
+
+fn token_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(apl_token::state::Account::unpack(&token_account.data.borrow())?.amount)
+}
+
+/// Checks that `token_account` holds `expected` tokens, `None` being an expected balance that
+/// overflowed or underflowed
+fn check_token_amount(
+    token_account: &AccountInfo,
+    expected: Option<u64>,
+) -> Result<(), ProgramError> {
+    let amount = token_amount(token_account)?;
+    if Some(amount) != expected {
+        msg!(
+            "account {:?} holds {} tokens, expected {:?}",
+            token_account.key,
+            amount,
+            expected
+        );
+        return Err(EscrowError::BalanceMismatch.into());
+    }
 
     Ok(())
 }
@@ -1,151 +1,1458 @@
-pub const ELF_PATH: &str = "./program/target/sbpf-solana-solana/release/escrow_program.so";
+pub mod offer_index;
+
+pub const ELF_PATH: &str = "./target/sbpf-solana-solana/release/escrow_program.so";
+
+pub const MINING_ADDRESS: &str = "bcrt1q9s6pf9hswah20jjnzmyvk9s2xwp7srz6m2r5tw";
 
 /// Running Tests
 #[cfg(test)]
 mod tests {
-    use crate::ELF_PATH;
+    use std::{
+        thread,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::offer_index::{OfferFilter, OfferIndex, Price};
+    use crate::{ELF_PATH, MINING_ADDRESS};
     use arch_program::{
-        account::AccountMeta, program_pack::Pack, pubkey::Pubkey, rent::minimum_rent,
-        sanitized::ArchMessage, utxo::UtxoMeta,
+        account::AccountMeta, instruction::Instruction, program_pack::Pack, pubkey::Pubkey,
+        rent::minimum_rent, sanitized::ArchMessage, utxo::UtxoMeta,
     };
     use arch_sdk::blocking::{ArchRpcClient, BitcoinHelper, ProgramDeployer};
     use arch_sdk::{
         build_and_sign_transaction, generate_new_keypair, with_secret_key_file, Config, Status,
     };
 
-    use bitcoin::key::Keypair;
-    use borsh::{BorshDeserialize, BorshSerialize};
+    use bitcoin::{
+        absolute::LockTime, address::NetworkChecked, key::Keypair, transaction::Version, Address,
+        Amount, Network, Transaction, TxOut,
+    };
+    use bitcoincore_rpc::{Auth, Client, RpcApi};
+    use escrow_interface::{
+        bitcoin_payment_commitment,
+        error::EscrowError,
+        find_associated_token_address, find_fee_config_address, find_offer_address,
+        instruction::{
+            cancel_offer_ix, make_offer_ix, refund_expired_offer_ix, set_fee_config_ix,
+            take_btc_offer_ix, take_offer_ix, MakeOffer, SetFeeConfig,
+        },
+        state::{Offer, OfferExpiry},
+        MAX_FEE_BPS, MIN_PAYMENT_CONFIRMATIONS,
+    };
+
+    #[ignore]
+    #[test]
+    fn escrow_test() {
+        println!("Program Deployment & Escros Program Initialization",);
+        println!("Deploying the Escrow program",);
+
+        let fixture = setup_offer_fixture(1);
+        let client = fixture.client.clone();
+
+        make_offer(&fixture);
+
+        fund_maker(&fixture, fixture.mint_b, 0);
+        let taker = setup_taker(&fixture, 0);
+
+        let maker_lamports = lamports(fixture.maker_pubkey, &client);
+        let offer_lamports = lamports(fixture.offer_pda.0, &client);
+        let vault_lamports = lamports(fixture.vault, &client);
+
+        take_offer(&fixture, &taker);
+
+        println!("The maker should get back the rent of the offer and of the vault");
+
+        assert_eq!(
+            lamports(fixture.maker_pubkey, &client),
+            maker_lamports + offer_lamports + vault_lamports
+        );
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert_eq!(offer_info.lamports, 0);
+        assert_eq!(offer_info.owner, Pubkey::system_program());
+    }
+
+    /// Deployed program, both mints, the offer address and its vault, before any offer is made
+    pub struct OfferFixture {
+        pub id: u64,
+        pub client: ArchRpcClient,
+        pub program_pubkey: Pubkey,
+        pub maker_pubkey: Pubkey,
+        pub maker_keypair: Keypair,
+        pub mint_a: Pubkey,
+        pub mint_b: Pubkey,
+        pub offer_pda: (Pubkey, u8),
+        pub offer_utxo: UtxoMeta,
+        pub vault: Pubkey,
+    }
+
+    pub fn setup_offer_fixture(id: u64) -> OfferFixture {
+        setup_offer_fixture_on_program(".program", id)
+    }
+
+    /// Same as `setup_offer_fixture` on the escrow program deployed with the key of
+    /// `program_key_file`, for the tests changing the fee of their program. The fee config of the
+    /// program is created at no fee the first time.
+    pub fn setup_offer_fixture_on_program(program_key_file: &str, id: u64) -> OfferFixture {
+        let config = Config::localnet();
+
+        let client = ArchRpcClient::new(&config);
+
+        let (maker_keypair, maker_pubkey, _) = generate_new_keypair(config.network);
+
+        client
+            .create_and_fund_program_authority_with_faucet(&maker_keypair)
+            .unwrap();
+
+        let (program_keypair, _) =
+            with_secret_key_file(program_key_file).expect("getting caller info should not fail");
+
+        let deployer = ProgramDeployer::new(&config);
+
+        let program_pubkey = deployer
+            .try_deploy_program(
+                "Escrow Program".to_string(),
+                program_keypair,
+                maker_keypair,
+                &ELF_PATH.to_string(),
+            )
+            .unwrap();
+
+        let mint_a = create_mint(&maker_pubkey, maker_keypair, client.clone());
+        let mint_b = create_mint(&maker_pubkey, maker_keypair, client.clone());
+
+        let offer_pda = find_offer_address(&program_pubkey, &maker_pubkey, id);
+
+        let helper = BitcoinHelper::new(&config).expect("Failed to create BitcoinHelper");
+
+        let (offer_txid, offer_vout) = helper.send_utxo(offer_pda.0).unwrap();
+        let offer_utxo = UtxoMeta::from(
+            hex::decode(offer_txid.clone()).unwrap().try_into().unwrap(),
+            offer_vout,
+        );
+
+        let vault = create_ata(
+            maker_pubkey,
+            offer_pda.0,
+            maker_keypair,
+            mint_a,
+            client.clone(),
+        );
+
+        let fixture = OfferFixture {
+            id,
+            client,
+            program_pubkey,
+            maker_pubkey,
+            maker_keypair,
+            mint_a,
+            mint_b,
+            offer_pda,
+            offer_utxo,
+            vault,
+        };
+
+        // tests running in parallel on the same program may create it first
+        if fee_config_missing(&fixture) {
+            let (_, fee_admin_pubkey) = fee_admin(&fixture.client);
+            set_fee_config(&fixture, fee_admin_pubkey, 0);
+        }
+
+        fixture
+    }
+
+    /// Admin of the fee config of the programs deployed by the tests, funded the first time
+    pub fn fee_admin(client: &ArchRpcClient) -> (Keypair, Pubkey) {
+        let (keypair, pubkey) =
+            with_secret_key_file(".fee_admin").expect("getting caller info should not fail");
+
+        if client.read_account_info(pubkey).is_err() {
+            client
+                .create_and_fund_account_with_faucet(&keypair)
+                .unwrap();
+        }
+
+        (keypair, pubkey)
+    }
+
+    fn fee_config_missing(fixture: &OfferFixture) -> bool {
+        fixture
+            .client
+            .read_account_info(find_fee_config_address(&fixture.program_pubkey).0)
+            .map_or(true, |fee_config_info| fee_config_info.data.is_empty())
+    }
+
+    /// Creates the fee config of the fixture program with the fee admin, sending the UTXO it is
+    /// created with, or updates it
+    pub fn set_fee_config(fixture: &OfferFixture, fee_recipient: Pubkey, fee_bps: u16) -> Status {
+        let config = Config::localnet();
+        let (admin_keypair, admin_pubkey) = fee_admin(&fixture.client);
+
+        let fee_config_utxo = fee_config_missing(fixture).then(|| {
+            let helper = BitcoinHelper::new(&config).expect("Failed to create BitcoinHelper");
+            let (txid, vout) = helper
+                .send_utxo(find_fee_config_address(&fixture.program_pubkey).0)
+                .unwrap();
+            UtxoMeta::from(hex::decode(txid).unwrap().try_into().unwrap(), vout)
+        });
+
+        send_instruction(
+            set_fee_config_ix(
+                &fixture.program_pubkey,
+                &admin_pubkey,
+                SetFeeConfig {
+                    fee_config_utxo,
+                    fee_recipient,
+                    fee_bps,
+                },
+            ),
+            admin_pubkey,
+            vec![admin_keypair],
+            fixture.client.clone(),
+        )
+    }
+
+    /// Funded taker with token accounts for both fixture mints
+    pub struct OfferTaker {
+        pub pubkey: Pubkey,
+        pub keypair: Keypair,
+        pub ata_a: Pubkey,
+        pub ata_b: Pubkey,
+    }
+
+    /// Funds a new taker, creates its token accounts for both fixture mints and mints it
+    /// `token_b_amount` of token B
+    pub fn setup_taker(fixture: &OfferFixture, token_b_amount: u64) -> OfferTaker {
+        let config = Config::localnet();
+        let client = fixture.client.clone();
+
+        let (keypair, pubkey, _) = generate_new_keypair(config.network);
+        client
+            .create_and_fund_account_with_faucet(&keypair)
+            .unwrap();
+
+        let ata_a = create_ata(pubkey, pubkey, keypair, fixture.mint_a, client.clone());
+        let ata_b = create_ata(pubkey, pubkey, keypair, fixture.mint_b, client.clone());
+
+        if token_b_amount > 0 {
+            mint_to(
+                token_b_amount,
+                fixture.mint_b,
+                ata_b,
+                fixture.maker_pubkey,
+                fixture.maker_keypair,
+                client,
+            );
+        }
+
+        OfferTaker {
+            pubkey,
+            keypair,
+            ata_a,
+            ata_b,
+        }
+    }
+
+    /// Creates the fixture maker token account for `mint` and mints it `amount` tokens
+    pub fn fund_maker(fixture: &OfferFixture, mint: Pubkey, amount: u64) -> Pubkey {
+        let client = fixture.client.clone();
+
+        let maker_ata = create_ata(
+            fixture.maker_pubkey,
+            fixture.maker_pubkey,
+            fixture.maker_keypair,
+            mint,
+            client.clone(),
+        );
+
+        if amount > 0 {
+            mint_to(
+                amount,
+                mint,
+                maker_ata,
+                fixture.maker_pubkey,
+                fixture.maker_keypair,
+                client,
+            );
+        }
+
+        maker_ata
+    }
+
+    #[ignore]
+    #[test]
+    fn make_offer_validation_fail() {
+        println!(
+            "Offer creation with invalid accounts, each attempt should fail with its own error"
+        );
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        let maker_ata_a = fund_maker(&fixture, fixture.mint_a, 100);
+
+        let make_offer = |offer_id: u64, offer_bump_seed: u8| {
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                MakeOffer {
+                    offer_bump_seed,
+                    offer_utxo: fixture.offer_utxo,
+                    id: offer_id,
+                    token_a_offered_amount: 100,
+                    token_b_wanted_amount: 100,
+                    expiry: None,
+                    btc_script_pubkey: None,
+                    allowed_taker: None,
+                },
+            )
+        };
+        let send = |instruction: Instruction| {
+            send_instruction(
+                instruction,
+                fixture.maker_pubkey,
+                vec![fixture.maker_keypair],
+                client.clone(),
+            )
+        };
+
+        println!("Token B mint replaced by an account the token program doesn't own");
+        let mut instruction = make_offer(id, fixture.offer_pda.1);
+        instruction.accounts[2].pubkey = fixture.maker_pubkey;
+        assert_escrow_error(&send(instruction), EscrowError::InvalidOwner);
+
+        println!("Offer id not matching the offer address");
+        let mut instruction = make_offer(id + 1, fixture.offer_pda.1);
+        instruction.accounts[0].pubkey = fixture.offer_pda.0;
+        instruction.accounts[4].pubkey = fixture.vault;
+        assert_escrow_error(&send(instruction), EscrowError::InvalidOfferAddress);
+
+        println!("Bump seed not matching the offer address");
+        let instruction = make_offer(id, fixture.offer_pda.1.wrapping_sub(1));
+        assert_escrow_error(&send(instruction), EscrowError::InvalidOfferAddress);
+
+        println!("Vault replaced by the maker token A account");
+        let mut instruction = make_offer(id, fixture.offer_pda.1);
+        instruction.accounts[4].pubkey = maker_ata_a;
+        assert_escrow_error(&send(instruction), EscrowError::InvalidVault);
+
+        println!("Valid offer, then the same offer a second time");
+        assert!(send(make_offer(id, fixture.offer_pda.1)) == Status::Processed);
+        assert_escrow_error(
+            &send(make_offer(id, fixture.offer_pda.1)),
+            EscrowError::OfferAlreadyInitialized,
+        );
+    }
+
+    #[ignore]
+    #[test]
+    fn take_offer_validation_fail() {
+        println!("Offer taken with invalid accounts, each attempt should fail with its own error and the valid one should go through");
+
+        let config = Config::localnet();
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        make_offer(&fixture);
+
+        fund_maker(&fixture, fixture.mint_b, 0);
+        let taker = setup_taker(&fixture, 100);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let take_instruction =
+            || take_offer_ix(&fixture.program_pubkey, &offer, &taker.pubkey, 100);
+
+        println!("Taker not signing, the maker paying for the transaction");
+        let mut instruction = take_instruction();
+        instruction.accounts[8].is_signer = false;
+        let status = send_instruction(
+            instruction,
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::MissingSigner);
+
+        println!("Offer account passed read only");
+        let mut instruction = take_instruction();
+        instruction.accounts[0].is_writable = false;
+        let status = send_instruction(
+            instruction,
+            taker.pubkey,
+            vec![taker.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::AccountNotWritable);
+
+        println!("Vault passed read only");
+        let mut instruction = take_instruction();
+        instruction.accounts[6].is_writable = false;
+        let status = send_instruction(
+            instruction,
+            taker.pubkey,
+            vec![taker.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::AccountNotWritable);
+
+        println!("Token B mint replaced by the token A mint");
+        let mut instruction = take_instruction();
+        instruction.accounts[2].pubkey = fixture.mint_a;
+        let status = send_instruction(
+            instruction,
+            taker.pubkey,
+            vec![taker.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidMint);
+
+        println!("Vault replaced by the taker token A account");
+        let mut instruction = take_instruction();
+        instruction.accounts[6].pubkey = taker.ata_a;
+        let status = send_instruction(
+            instruction,
+            taker.pubkey,
+            vec![taker.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidVault);
+
+        println!("Token B paid to another account than the maker one");
+        let (_, accomplice_pubkey, _) = generate_new_keypair(config.network);
+        let accomplice_ata_b = create_ata(
+            taker.pubkey,
+            accomplice_pubkey,
+            taker.keypair,
+            fixture.mint_b,
+            client.clone(),
+        );
+        let mut instruction = take_instruction();
+        instruction.accounts[3].pubkey = accomplice_ata_b;
+        let status = send_instruction(
+            instruction,
+            taker.pubkey,
+            vec![taker.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidTokenAccount);
+
+        println!("Token B replaced by a worthless token C the taker minted");
+        let mint_c = create_mint(&taker.pubkey, taker.keypair, client.clone());
+        let maker_ata_c = create_ata(
+            taker.pubkey,
+            fixture.maker_pubkey,
+            taker.keypair,
+            mint_c,
+            client.clone(),
+        );
+        let taker_ata_c = create_ata(
+            taker.pubkey,
+            taker.pubkey,
+            taker.keypair,
+            mint_c,
+            client.clone(),
+        );
+        mint_to(
+            100,
+            mint_c,
+            taker_ata_c,
+            taker.pubkey,
+            taker.keypair,
+            client.clone(),
+        );
+        let mut instruction = take_instruction();
+        instruction.accounts[3].pubkey = maker_ata_c;
+        instruction.accounts[5].pubkey = taker_ata_c;
+        let status = send_instruction(
+            instruction,
+            taker.pubkey,
+            vec![taker.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidTokenAccount);
+
+        println!("Valid take");
+        take_offer(&fixture, &taker);
+    }
+
+    #[ignore]
+    #[test]
+    fn take_offer_partial_fills_test() {
+        println!("Offer of 100 A for 30 B filled in three takes, token B should be paid pro rata rounded up and the offer closed after the last fill");
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        fund_maker(&fixture, fixture.mint_a, 100);
+
+        let status = send_instruction(
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                MakeOffer {
+                    offer_bump_seed: fixture.offer_pda.1,
+                    offer_utxo: fixture.offer_utxo,
+                    id,
+                    token_a_offered_amount: 100,
+                    token_b_wanted_amount: 30,
+                    expiry: None,
+                    btc_script_pubkey: None,
+                    allowed_taker: None,
+                },
+            ),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert!(status == Status::Processed);
+
+        let maker_ata_b = fund_maker(&fixture, fixture.mint_b, 0);
+        let taker = setup_taker(&fixture, 30);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let take = |token_a_amount: u64| {
+            send_instruction(
+                take_offer_ix(
+                    &fixture.program_pubkey,
+                    &offer,
+                    &taker.pubkey,
+                    token_a_amount,
+                ),
+                taker.pubkey,
+                vec![taker.keypair],
+                client.clone(),
+            )
+        };
+        println!("Empty fill");
+        assert_escrow_error(&take(0), EscrowError::InvalidAmount);
+
+        println!("Taking half of the offer, for exactly 15 B");
+        assert!(take(50) == Status::Processed);
+        assert_eq!(token_balance(&client, taker.ata_a), 50);
+        assert_eq!(token_balance(&client, maker_ata_b), 15);
+        assert_eq!(token_balance(&client, fixture.vault), 50);
+
+        println!("Taking 1 A, worth 0.3 B, rounded up to 1 B");
+        assert!(take(1) == Status::Processed);
+        assert_eq!(token_balance(&client, taker.ata_a), 51);
+        assert_eq!(token_balance(&client, maker_ata_b), 16);
+
+        println!("Taking more than what is left");
+        assert_escrow_error(&take(50), EscrowError::InvalidAmount);
+
+        println!("Taking the remaining 49 A, for the remaining 14 B");
+        assert!(take(49) == Status::Processed);
+        assert_eq!(token_balance(&client, taker.ata_a), 100);
+        assert_eq!(token_balance(&client, maker_ata_b), 30);
+        assert_eq!(token_balance(&client, taker.ata_b), 0);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+    }
+
+    #[ignore]
+    #[test]
+    fn take_offer_sweeps_vault_donation_test() {
+        println!("Offer of 100 A for 100 B whose vault got 7 A on top of the offer, the last fill should return them to the maker and close the vault");
+
+        let fixture = setup_offer_fixture(1);
+        let client = fixture.client.clone();
+
+        make_offer(&fixture);
+
+        let maker_ata_a = find_associated_token_address(&fixture.maker_pubkey, &fixture.mint_a);
+        fund_maker(&fixture, fixture.mint_b, 0);
+        let taker = setup_taker(&fixture, 100);
+
+        mint_to(
+            7,
+            fixture.mint_a,
+            fixture.vault,
+            fixture.maker_pubkey,
+            fixture.maker_keypair,
+            client.clone(),
+        );
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let take = |token_a_amount: u64| {
+            send_instruction(
+                take_offer_ix(
+                    &fixture.program_pubkey,
+                    &offer,
+                    &taker.pubkey,
+                    token_a_amount,
+                ),
+                taker.pubkey,
+                vec![taker.keypair],
+                client.clone(),
+            )
+        };
+
+        println!("Taking 40 A, the donation stays in the vault");
+        assert!(take(40) == Status::Processed);
+        assert_eq!(token_balance(&client, fixture.vault), 67);
+        assert_eq!(token_balance(&client, maker_ata_a), 0);
+
+        println!("Taking the remaining 60 A, the 7 A left go back to the maker");
+        assert!(take(60) == Status::Processed);
+        assert_eq!(token_balance(&client, taker.ata_a), 100);
+        assert_eq!(token_balance(&client, maker_ata_a), 7);
+        assert_eq!(lamports(fixture.vault, &client), 0);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+    }
+
+    #[ignore]
+    #[test]
+    fn take_offer_with_existing_balances_test() {
+        println!("Offer of 100 A for 100 B taken while the taker and the maker already hold tokens, every balance should move by the traded amounts only");
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        make_offer(&fixture);
+
+        let maker_ata_b = fund_maker(&fixture, fixture.mint_b, 0);
+        let taker = setup_taker(&fixture, 0);
+
+        for (amount, mint, token_account) in [
+            (40, fixture.mint_b, maker_ata_b),
+            (25, fixture.mint_a, taker.ata_a),
+            (60, fixture.mint_b, taker.ata_b),
+        ] {
+            mint_to(
+                amount,
+                mint,
+                token_account,
+                fixture.maker_pubkey,
+                fixture.maker_keypair,
+                client.clone(),
+            );
+        }
+
+        // take_offer mints the 100 B it pays, the taker should be left with its 60 B
+        take_offer(&fixture, &taker);
+
+        assert_eq!(token_balance(&client, maker_ata_b), 140);
+        assert_eq!(token_balance(&client, taker.ata_a), 125);
+        assert_eq!(token_balance(&client, taker.ata_b), 60);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+    }
+
+    #[ignore]
+    #[test]
+    fn cancel_offer_test() {
+        println!(
+            "Offer cancellation, the maker should get token A back and the offer should be cleared"
+        );
+
+        let config = Config::localnet();
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        make_offer(&fixture);
+
+        let maker_ata_a = find_associated_token_address(&fixture.maker_pubkey, &fixture.mint_a);
+
+        assert_eq!(token_balance(&client, maker_ata_a), 0);
+        assert_eq!(token_balance(&client, fixture.vault), 100);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let cancel_offer = || cancel_offer_ix(&fixture.program_pubkey, &offer);
+
+        println!("Cancellation signed by someone else than the maker");
+
+        let (other_keypair, other_pubkey, _) = generate_new_keypair(config.network);
+        client
+            .create_and_fund_account_with_faucet(&other_keypair)
+            .unwrap();
+
+        let mut instruction = cancel_offer();
+        instruction.accounts[4].pubkey = other_pubkey;
+        let status = send_instruction(
+            instruction,
+            other_pubkey,
+            vec![other_keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidOfferAddress);
+
+        println!("Cancellation signed by the maker, the fees being paid by someone else");
+
+        let maker_lamports = lamports(fixture.maker_pubkey, &client);
+        let offer_lamports = lamports(fixture.offer_pda.0, &client);
+        let vault_lamports = lamports(fixture.vault, &client);
+
+        let status = send_instruction(
+            cancel_offer(),
+            other_pubkey,
+            vec![other_keypair, fixture.maker_keypair],
+            client.clone(),
+        );
+        assert!(status == Status::Processed);
+
+        assert_eq!(token_balance(&client, maker_ata_a), 100);
+        assert_eq!(
+            lamports(fixture.maker_pubkey, &client),
+            maker_lamports + offer_lamports + vault_lamports
+        );
+
+        let vault_closed = client
+            .read_account_info(fixture.vault)
+            .map_or(true, |vault_info| {
+                apl_token::state::Account::unpack(&vault_info.data).is_err()
+            });
+        assert!(vault_closed);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+        assert_eq!(offer_info.lamports, 0);
+        assert_eq!(offer_info.owner, Pubkey::system_program());
+    }
+
+    #[ignore]
+    #[test]
+    fn expired_offer_refund_test() {
+        println!("Offer expiring at the next Bitcoin block, it can't be taken once expired and anyone can refund the maker");
+
+        let config = Config::localnet();
+
+        let rpc = Client::new(
+            &config.node_endpoint,
+            Auth::UserPass(config.node_username.clone(), config.node_password.clone()),
+        )
+        .expect("rpc shouldn not fail to be initiated");
+        let expiry_height = rpc.get_block_count().unwrap() + 1;
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        let maker_ata_a = fund_maker(&fixture, fixture.mint_a, 100);
+
+        let status = send_instruction(
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                MakeOffer {
+                    offer_bump_seed: fixture.offer_pda.1,
+                    offer_utxo: fixture.offer_utxo,
+                    id,
+                    token_a_offered_amount: 100,
+                    token_b_wanted_amount: 100,
+                    expiry: Some(OfferExpiry::BitcoinBlockHeight(expiry_height)),
+                    btc_script_pubkey: None,
+                    allowed_taker: None,
+                },
+            ),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert!(status == Status::Processed);
+
+        let other = setup_taker(&fixture, 100);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let refund = || {
+            send_instruction(
+                refund_expired_offer_ix(&fixture.program_pubkey, &offer),
+                other.pubkey,
+                vec![other.keypair],
+                client.clone(),
+            )
+        };
+
+        println!("Refund before expiry");
+        assert_escrow_error(&refund(), EscrowError::OfferNotExpired);
+
+        println!("Mining a block to reach the expiry height");
+        mine_block();
+        thread::sleep(Duration::from_secs(10));
+
+        fund_maker(&fixture, fixture.mint_b, 0);
+
+        println!("Taking the expired offer");
+        let status = send_instruction(
+            take_offer_ix(&fixture.program_pubkey, &offer, &other.pubkey, 100),
+            other.pubkey,
+            vec![other.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::OfferExpired);
+
+        println!("Refund after expiry to a token A account of someone else than the maker");
+        let mut instruction = refund_expired_offer_ix(&fixture.program_pubkey, &offer);
+        instruction.accounts[2].pubkey = other.ata_a;
+        let status = send_instruction(
+            instruction,
+            other.pubkey,
+            vec![other.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidTokenAccount);
+        assert_eq!(token_balance(&client, other.ata_a), 0);
+
+        println!("Refund after expiry, signed by someone else than the maker");
+        assert!(refund() == Status::Processed);
+
+        assert_eq!(token_balance(&client, maker_ata_a), 100);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+    }
+
+    #[ignore]
+    #[test]
+    fn take_btc_offer_test() {
+        println!("Offer of 100 A for 5000 sats, the taker's Bitcoin payment should pay the maker script, commit to the offer alone, be mined and get its confirmations before settling it");
+
+        let config = Config::localnet();
+
+        let rpc = Client::new(
+            &config.node_endpoint,
+            Auth::UserPass(config.node_username.clone(), config.node_password.clone()),
+        )
+        .expect("rpc shouldn not fail to be initiated");
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        let maker_script_pubkey = MINING_ADDRESS
+            .parse::<Address<_>>()
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap()
+            .script_pubkey();
+        let sats_wanted: u64 = 5_000;
+
+        fund_maker(&fixture, fixture.mint_a, 100);
+
+        // native bitcoin has no mint, the system program stands for it
+        let instruction = make_offer_ix(
+            &fixture.program_pubkey,
+            &fixture.maker_pubkey,
+            &fixture.mint_a,
+            &Pubkey::system_program(),
+            MakeOffer {
+                offer_bump_seed: fixture.offer_pda.1,
+                offer_utxo: fixture.offer_utxo,
+                id,
+                token_a_offered_amount: 100,
+                token_b_wanted_amount: sats_wanted,
+                expiry: None,
+                btc_script_pubkey: Some(maker_script_pubkey.to_bytes()),
+                allowed_taker: None,
+            },
+        );
+        let status = send_instruction(
+            instruction,
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert!(status == Status::Processed);
+
+        let taker = setup_taker(&fixture, 0);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let take = |payment: UtxoMeta| {
+            send_instruction(
+                take_btc_offer_ix(&fixture.program_pubkey, &offer, &taker.pubkey, payment),
+                taker.pubkey,
+                vec![taker.keypair],
+                client.clone(),
+            )
+        };
+
+        let commitment_output = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: bitcoin_payment_commitment(&fixture.offer_utxo),
+        };
+        let payment_output = |sats: u64| TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey: maker_script_pubkey.clone(),
+        };
+
+        println!("Payment that doesn't commit to the offer");
+        let payment = send_btc_payment(&rpc, vec![payment_output(sats_wanted)]);
+        assert_escrow_error(&take(payment), EscrowError::InvalidBitcoinPayment);
+
+        println!("Payment of one sat less than asked");
+        let payment = send_btc_payment(
+            &rpc,
+            vec![payment_output(sats_wanted - 1), commitment_output.clone()],
+        );
+        assert_escrow_error(&take(payment), EscrowError::InvalidBitcoinPayment);
+
+        println!("Payment committing to the offer and to another one paid to the same script");
+        let other_offer_utxo = UtxoMeta::from([1; 32], 0);
+        let payment = send_btc_payment(
+            &rpc,
+            vec![
+                payment_output(sats_wanted),
+                commitment_output.clone(),
+                TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: bitcoin_payment_commitment(&other_offer_utxo),
+                },
+            ],
+        );
+        assert_escrow_error(&take(payment), EscrowError::InvalidBitcoinPayment);
+
+        println!("Payment of the asked sats, committing to the offer, not mined yet");
+        let payment =
+            broadcast_btc_payment(&rpc, vec![payment_output(sats_wanted), commitment_output]);
+        thread::sleep(Duration::from_secs(10));
+        assert_escrow_error(&take(payment), EscrowError::BitcoinPaymentUnconfirmed);
+        assert!(read_offer(fixture.offer_pda.0, &client)
+            .btc_payment
+            .is_none());
+
+        println!("Same payment once mined, registered by the first take");
+        mine_block();
+        thread::sleep(Duration::from_secs(10));
+        assert!(take(payment) == Status::Processed);
+
+        assert_eq!(token_balance(&client, taker.ata_a), 0);
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let btc_payment = offer.btc_payment.clone().unwrap();
+        assert_eq!(btc_payment.payment, payment);
+        assert_eq!(btc_payment.taker, taker.pubkey);
+
+        println!("Settling before the payment got its confirmations");
+        assert_escrow_error(&take(payment), EscrowError::BitcoinPaymentPending);
+
+        println!("Cancelling once the taker paid");
+        let status = send_instruction(
+            cancel_offer_ix(&fixture.program_pubkey, &offer),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::BitcoinPaymentPending);
+
+        println!("Settling with the payment registered by another taker");
+        let other = setup_taker(&fixture, 0);
+        let status = send_instruction(
+            take_btc_offer_ix(&fixture.program_pubkey, &offer, &other.pubkey, payment),
+            other.pubkey,
+            vec![other.keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidBitcoinPayment);
+
+        println!("Mining the payment confirmations");
+        for _ in 0..MIN_PAYMENT_CONFIRMATIONS {
+            mine_block();
+        }
+        thread::sleep(Duration::from_secs(10));
+
+        println!("3 A sent to the vault on top of the offer");
+        let maker_ata_a = find_associated_token_address(&fixture.maker_pubkey, &fixture.mint_a);
+        mint_to(
+            3,
+            fixture.mint_a,
+            fixture.vault,
+            fixture.maker_pubkey,
+            fixture.maker_keypair,
+            client.clone(),
+        );
+
+        println!("Settling the confirmed payment, the 3 A going back to the maker");
+        assert!(take(payment) == Status::Processed);
+
+        assert_eq!(token_balance(&client, taker.ata_a), 100);
+        assert_eq!(token_balance(&client, maker_ata_a), 3);
+        assert_eq!(lamports(fixture.vault, &client), 0);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+    }
+
+    #[ignore]
+    #[test]
+    fn offer_index_test() {
+        println!("Offers from two makers on both directions of a mint pair, queried by maker, mint pair and price, a cancelled offer shouldn't be listed anymore and an expired one only when asked for");
+
+        let config = Config::localnet();
+
+        let rpc = Client::new(
+            &config.node_endpoint,
+            Auth::UserPass(config.node_username.clone(), config.node_password.clone()),
+        )
+        .expect("rpc shouldn not fail to be initiated");
+        let expiry_height = rpc.get_block_count().unwrap() + 1;
+
+        let fixture = setup_offer_fixture(1);
+        let client = fixture.client.clone();
+        let maker_pubkey = fixture.maker_pubkey;
+
+        let (other_maker_keypair, other_maker_pubkey, _) = generate_new_keypair(config.network);
+        client
+            .create_and_fund_account_with_faucet(&other_maker_keypair)
+            .unwrap();
+
+        for (maker_pubkey, maker_keypair, mint) in [
+            (maker_pubkey, fixture.maker_keypair, fixture.mint_a),
+            (maker_pubkey, fixture.maker_keypair, fixture.mint_b),
+            (other_maker_pubkey, other_maker_keypair, fixture.mint_a),
+        ] {
+            create_ata(
+                maker_pubkey,
+                maker_pubkey,
+                maker_keypair,
+                mint,
+                client.clone(),
+            );
+        }
 
-    #[derive(BorshDeserialize, BorshSerialize, Debug)]
-    pub struct MakeOffer {
-        /// The bump seed for the offer's Program Derived Address
-        pub offer_bump_seed: u8,
-        /// The UTXO metadata associated with this offer
-        pub offer_utxo: UtxoMeta,
-        /// Unique identifier for the offer
-        pub id: u64,
-        /// Amount of token A being offered
-        pub token_a_offered_amount: u64,
-        /// Amount of token B wanted in exchange
-        pub token_b_wanted_amount: u64,
-    }
+        // the fixture already created the vault of offer 1 of the maker, who uses the next ids
+        let (mint_a, mint_b) = (fixture.mint_a, fixture.mint_b);
+        open_offer(
+            &fixture,
+            maker_pubkey,
+            fixture.maker_keypair,
+            (mint_a, mint_b),
+            2,
+            (100, 30),
+            None,
+        );
+        open_offer(
+            &fixture,
+            maker_pubkey,
+            fixture.maker_keypair,
+            (mint_a, mint_b),
+            3,
+            (100, 200),
+            None,
+        );
+        open_offer(
+            &fixture,
+            maker_pubkey,
+            fixture.maker_keypair,
+            (mint_b, mint_a),
+            4,
+            (50, 10),
+            None,
+        );
+        open_offer(
+            &fixture,
+            other_maker_pubkey,
+            other_maker_keypair,
+            (mint_a, mint_b),
+            1,
+            (10, 5),
+            None,
+        );
+        open_offer(
+            &fixture,
+            maker_pubkey,
+            fixture.maker_keypair,
+            (mint_a, mint_b),
+            5,
+            (20, 40),
+            Some(OfferExpiry::BitcoinBlockHeight(expiry_height)),
+        );
+
+        let query = |filter: OfferFilter| {
+            let index = OfferIndex::scan(&client, &fixture.program_pubkey, &filter).unwrap();
+            let mut offers: Vec<(u64, u64)> = index
+                .query(&filter)
+                .iter()
+                .map(|open_offer| (open_offer.offer.id, open_offer.offer.token_a_offered_amount))
+                .collect();
+            offers.sort();
+            offers
+        };
+
+        println!("Offers by maker");
+        assert_eq!(
+            query(OfferFilter::default().maker(maker_pubkey)),
+            [(2, 100), (3, 100), (4, 50), (5, 20)]
+        );
+        assert_eq!(
+            query(OfferFilter::default().maker(other_maker_pubkey)),
+            [(1, 10)]
+        );
+
+        println!("Offers of token A for token B");
+        assert_eq!(
+            query(OfferFilter::default().mint_pair(mint_a, mint_b)),
+            [(1, 10), (2, 100), (3, 100), (5, 20)]
+        );
+
+        println!("Offers of token A for at most one token B each");
+        assert_eq!(
+            query(
+                OfferFilter::default()
+                    .mint_pair(mint_a, mint_b)
+                    .max_price(Price::new(1, 1))
+            ),
+            [(1, 10), (2, 100)]
+        );
+
+        println!("Cancelled offer");
+        let offer = read_offer(
+            find_offer_address(&fixture.program_pubkey, &maker_pubkey, 3).0,
+            &client,
+        );
+        let status = send_instruction(
+            cancel_offer_ix(&fixture.program_pubkey, &offer),
+            maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert!(status == Status::Processed);
 
-    #[derive(BorshSerialize, BorshDeserialize, Debug)]
-    enum EscrowInstruction {
-        /// Create a new offer to exchange tokens
-        MakeOffer(MakeOffer),
-        /// Accept an existing offer
-        TakeOffer,
+        assert_eq!(
+            query(OfferFilter::default().maker(maker_pubkey)),
+            [(2, 100), (4, 50), (5, 20)]
+        );
+
+        println!("Mining a block to reach the expiry height of offer 5");
+        mine_block();
+        thread::sleep(Duration::from_secs(10));
+
+        let unix_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        assert_eq!(
+            query(
+                OfferFilter::default()
+                    .maker(maker_pubkey)
+                    .not_expired_at(expiry_height, unix_timestamp)
+            ),
+            [(2, 100), (4, 50)]
+        );
     }
 
     #[ignore]
     #[test]
-    fn escrow_test() {
+    fn take_offer_with_fee_test() {
+        println!("Offer of 100 A for 1000 B made on a program charging a fee, taken in four fills while the admin raises then lowers the fee, token B should be split between the maker and the fee recipient at the fee of the config when the offer was made, or the current one once lower");
+
         let config = Config::localnet();
 
-        println!("Program Deployment & Escros Program Initialization",);
-        println!("Deploying the Escrow program",);
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture_on_program(".fee_program", id);
+        let client = fixture.client.clone();
 
-        let client = ArchRpcClient::new(&config);
+        let (_, fee_recipient_pubkey, _) = generate_new_keypair(config.network);
 
-        let (maker_keypair, maker_pubkey, _) = generate_new_keypair(config.network);
+        println!("Fee above 100%");
+        assert_escrow_error(
+            &set_fee_config(&fixture, fee_recipient_pubkey, MAX_FEE_BPS + 1),
+            EscrowError::InvalidFeeConfig,
+        );
 
-        client
-            .create_and_fund_program_authority_with_faucet(&maker_keypair)
-            .unwrap();
+        println!("Fee config of 2.5%");
+        assert!(set_fee_config(&fixture, fee_recipient_pubkey, 250) == Status::Processed);
 
-        let (program_keypair, _) =
-            with_secret_key_file(".program").expect("getting caller info should not fail");
+        println!("Fee config updated by someone else than its admin");
+        let status = send_instruction(
+            set_fee_config_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                SetFeeConfig {
+                    fee_config_utxo: None,
+                    fee_recipient: fixture.maker_pubkey,
+                    fee_bps: 0,
+                },
+            ),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert_escrow_error(&status, EscrowError::InvalidFeeAdmin);
 
-        let deployer = ProgramDeployer::new(&config);
+        fund_maker(&fixture, fixture.mint_a, 100);
 
-        let program_pubkey = deployer
-            .try_deploy_program(
-                "Escrow Program".to_string(),
-                program_keypair,
-                maker_keypair,
-                &ELF_PATH.to_string(),
-            )
-            .unwrap();
+        let status = send_instruction(
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                MakeOffer {
+                    offer_bump_seed: fixture.offer_pda.1,
+                    offer_utxo: fixture.offer_utxo,
+                    id,
+                    token_a_offered_amount: 100,
+                    token_b_wanted_amount: 1000,
+                    expiry: None,
+                    btc_script_pubkey: None,
+                    allowed_taker: None,
+                },
+            ),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            client.clone(),
+        );
+        assert!(status == Status::Processed);
 
-        let mint_a = create_mint(&maker_pubkey, maker_keypair, client.clone());
-        let mint_b = create_mint(&maker_pubkey, maker_keypair, client.clone());
+        let maker_ata_b = fund_maker(&fixture, fixture.mint_b, 0);
+        let fee_recipient_ata_b = create_ata(
+            fixture.maker_pubkey,
+            fee_recipient_pubkey,
+            fixture.maker_keypair,
+            fixture.mint_b,
+            client.clone(),
+        );
+        let taker = setup_taker(&fixture, 1000);
 
-        let id: u64 = 1;
-        let offer_seeds = &[b"offer", maker_pubkey.as_ref(), &id.to_le_bytes()];
-        let expected_offer_pda = Pubkey::find_program_address(offer_seeds, &program_pubkey);
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        let fee = offer.fee.clone().unwrap();
+        assert_eq!(fee.fee_recipient, fee_recipient_pubkey);
+        assert_eq!(fee.fee_bps, 250);
 
-        let helper = BitcoinHelper::new(&config).expect("Failed to create BitcoinHelper");
+        let take_instruction = |token_a_amount: u64| {
+            take_offer_ix(
+                &fixture.program_pubkey,
+                &offer,
+                &taker.pubkey,
+                token_a_amount,
+            )
+        };
+        let send = |instruction: Instruction| {
+            send_instruction(
+                instruction,
+                taker.pubkey,
+                vec![taker.keypair],
+                client.clone(),
+            )
+        };
+        println!("Fee paid to the taker instead of the fee recipient");
+        let mut instruction = take_instruction(50);
+        instruction.accounts[14].pubkey = taker.ata_b;
+        assert_escrow_error(&send(instruction), EscrowError::InvalidFeeAccount);
 
-        let (offer_txid, offer_vout) = helper.send_utxo(expected_offer_pda.0).unwrap();
-        let offer_utxo = UtxoMeta::from(
-            hex::decode(offer_txid.clone()).unwrap().try_into().unwrap(),
-            offer_vout,
+        println!("Taking half of the offer for 500 B, 2.5% fee of 12.5 B rounded down to 12 B");
+        assert!(send(take_instruction(50)) == Status::Processed);
+        assert_eq!(token_balance(&client, maker_ata_b), 488);
+        assert_eq!(token_balance(&client, fee_recipient_ata_b), 12);
+
+        println!("Taking 1 A for 10 B, the fee owed on 510 B is still 12 B");
+        assert!(send(take_instruction(1)) == Status::Processed);
+        assert_eq!(token_balance(&client, maker_ata_b), 498);
+        assert_eq!(token_balance(&client, fee_recipient_ata_b), 12);
+
+        println!("Fee raised to 10% by the admin, the open offer keeps its 2.5% fee");
+        assert!(set_fee_config(&fixture, fee_recipient_pubkey, 1000) == Status::Processed);
+        assert_eq!(
+            read_offer(fixture.offer_pda.0, &client)
+                .fee
+                .unwrap()
+                .fee_bps,
+            250
         );
 
-        let vault = create_ata(
-            maker_pubkey,
-            expected_offer_pda.0,
-            maker_keypair,
-            mint_a,
-            client.clone(),
+        println!(
+            "Taking 19 A for 190 B, the fee owed on 700 B is 17 B, 12 B of it on the first 510 B"
         );
+        assert!(send(take_instruction(19)) == Status::Processed);
+        assert_eq!(token_balance(&client, maker_ata_b), 683);
+        assert_eq!(token_balance(&client, fee_recipient_ata_b), 17);
 
-        make_offer(
-            maker_pubkey,
-            maker_keypair,
-            mint_a,
-            mint_b,
-            vault,
-            expected_offer_pda,
-            offer_utxo,
-            id,
-            program_pubkey,
+        println!("Fee lowered to 1% by the admin, the open offer pays the lower fee");
+        assert!(set_fee_config(&fixture, fee_recipient_pubkey, 100) == Status::Processed);
+
+        println!("Taking the remaining 30 A for 300 B, at 1% the fee owed on 1000 B is 10 B, 7 B of it on the first 700 B");
+        assert!(send(take_instruction(30)) == Status::Processed);
+        assert_eq!(token_balance(&client, maker_ata_b), 980);
+        assert_eq!(token_balance(&client, fee_recipient_ata_b), 20);
+        assert_eq!(token_balance(&client, taker.ata_b), 0);
+
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
+    }
+
+    #[ignore]
+    #[test]
+    fn fee_cannot_be_skipped_test() {
+        println!("Offer of 100 A for 1000 B on a program charging a 2.5% fee, making or taking it without the fee config of the program or with another account in its place should fail, and the taker should pay the fee");
+
+        let config = Config::localnet();
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture_on_program(".fee_skip_program", id);
+        let client = fixture.client.clone();
+
+        let (_, fee_recipient_pubkey, _) = generate_new_keypair(config.network);
+        assert!(set_fee_config(&fixture, fee_recipient_pubkey, 250) == Status::Processed);
+
+        fund_maker(&fixture, fixture.mint_a, 100);
+
+        let make_instruction = || {
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                MakeOffer {
+                    offer_bump_seed: fixture.offer_pda.1,
+                    offer_utxo: fixture.offer_utxo,
+                    id,
+                    token_a_offered_amount: 100,
+                    token_b_wanted_amount: 1000,
+                    expiry: None,
+                    btc_script_pubkey: None,
+                    allowed_taker: None,
+                },
+            )
+        };
+        let send_as_maker = |instruction: Instruction| {
+            send_instruction(
+                instruction,
+                fixture.maker_pubkey,
+                vec![fixture.maker_keypair],
+                client.clone(),
+            )
+        };
+
+        println!("Offer made without the fee config");
+        let mut instruction = make_instruction();
+        instruction.accounts.pop();
+        assert!(matches!(send_as_maker(instruction), Status::Failed(_)));
+
+        println!("Offer made with an account of the maker as fee config");
+        let mut instruction = make_instruction();
+        instruction.accounts[9].pubkey = fixture.maker_pubkey;
+        assert_escrow_error(&send_as_maker(instruction), EscrowError::InvalidOwner);
+
+        assert!(send_as_maker(make_instruction()) == Status::Processed);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        assert_eq!(offer.fee.as_ref().map(|fee| fee.fee_bps), Some(250));
+
+        let maker_ata_b = fund_maker(&fixture, fixture.mint_b, 0);
+        let fee_recipient_ata_b = create_ata(
+            fixture.maker_pubkey,
+            fee_recipient_pubkey,
+            fixture.maker_keypair,
+            fixture.mint_b,
             client.clone(),
         );
+        let taker = setup_taker(&fixture, 1000);
 
-        let (taker_keypair, taker_pubkey, _) = generate_new_keypair(config.network);
-        client
-            .create_and_fund_account_with_faucet(&taker_keypair)
-            .unwrap();
+        let take_instruction =
+            || take_offer_ix(&fixture.program_pubkey, &offer, &taker.pubkey, 100);
+        let send_as_taker = |instruction: Instruction| {
+            send_instruction(
+                instruction,
+                taker.pubkey,
+                vec![taker.keypair],
+                client.clone(),
+            )
+        };
 
-        let maker_ata_b = create_ata(
-            maker_pubkey,
-            maker_pubkey,
-            maker_keypair,
-            mint_b,
+        println!("Offer taken without the fee config nor the fee token account");
+        let mut instruction = take_instruction();
+        instruction.accounts.truncate(13);
+        assert!(matches!(send_as_taker(instruction), Status::Failed(_)));
+
+        println!("Offer taken without the fee token account");
+        let mut instruction = take_instruction();
+        instruction.accounts.truncate(14);
+        assert!(matches!(send_as_taker(instruction), Status::Failed(_)));
+
+        println!("Offer taken with the offer account as fee config");
+        let mut instruction = take_instruction();
+        instruction.accounts[13].pubkey = fixture.offer_pda.0;
+        assert_escrow_error(&send_as_taker(instruction), EscrowError::InvalidFeeConfig);
+
+        assert_eq!(token_balance(&client, taker.ata_b), 1000);
+
+        println!("Taking the whole offer for 1000 B, 25 B of it for the fee");
+        assert!(send_as_taker(take_instruction()) == Status::Processed);
+        assert_eq!(token_balance(&client, maker_ata_b), 975);
+        assert_eq!(token_balance(&client, fee_recipient_ata_b), 25);
+    }
+
+    #[ignore]
+    #[test]
+    fn private_offer_test() {
+        println!("Offer of 100 A for 30 B reserved to a designated taker, another taker should be rejected and the designated one should take it");
+
+        let id: u64 = 1;
+        let fixture = setup_offer_fixture(id);
+        let client = fixture.client.clone();
+
+        fund_maker(&fixture, fixture.mint_a, 100);
+        let maker_ata_b = fund_maker(&fixture, fixture.mint_b, 0);
+
+        let taker = setup_taker(&fixture, 30);
+        let other = setup_taker(&fixture, 30);
+
+        let status = send_instruction(
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                MakeOffer {
+                    offer_bump_seed: fixture.offer_pda.1,
+                    offer_utxo: fixture.offer_utxo,
+                    id,
+                    token_a_offered_amount: 100,
+                    token_b_wanted_amount: 30,
+                    expiry: None,
+                    btc_script_pubkey: None,
+                    allowed_taker: Some(taker.pubkey),
+                },
+            ),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
             client.clone(),
         );
-        let taker_ata_a = create_ata(
-            taker_pubkey,
-            taker_pubkey,
-            taker_keypair,
-            mint_a,
+        assert!(status == Status::Processed);
+
+        let offer = read_offer(fixture.offer_pda.0, &client);
+        assert_eq!(offer.allowed_taker, Some(taker.pubkey));
+
+        println!("Taken by another taker than the designated one");
+        let status = send_instruction(
+            take_offer_ix(&fixture.program_pubkey, &offer, &other.pubkey, 100),
+            other.pubkey,
+            vec![other.keypair],
             client.clone(),
         );
-        let taker_ata_b = create_ata(
-            taker_pubkey,
-            taker_pubkey,
-            taker_keypair,
-            mint_b,
+        assert_escrow_error(&status, EscrowError::TakerNotAllowed);
+        assert_eq!(token_balance(&client, other.ata_b), 30);
+        assert_eq!(token_balance(&client, fixture.vault), 100);
+
+        println!("Taken by the designated taker");
+        let status = send_instruction(
+            take_offer_ix(&fixture.program_pubkey, &offer, &taker.pubkey, 100),
+            taker.pubkey,
+            vec![taker.keypair],
             client.clone(),
         );
+        assert!(status == Status::Processed);
+        assert_eq!(token_balance(&client, taker.ata_a), 100);
+        assert_eq!(token_balance(&client, taker.ata_b), 0);
+        assert_eq!(token_balance(&client, maker_ata_b), 30);
 
-        take_offer(
-            maker_ata_b,
-            taker_ata_a,
-            taker_ata_b,
-            mint_a,
-            mint_b,
-            maker_pubkey,
-            maker_keypair,
-            taker_pubkey,
-            taker_keypair,
-            vault,
-            expected_offer_pda,
-            program_pubkey,
-            client,
-        );
+        let offer_info = client.read_account_info(fixture.offer_pda.0).unwrap();
+        assert!(offer_info.data.is_empty());
     }
 
     pub fn create_mint(payer: &Pubkey, payer_keypair: Keypair, client: ArchRpcClient) -> Pubkey {
@@ -266,142 +1573,245 @@ mod tests {
         associated_account_address
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn make_offer(
-        maker_pubkey: Pubkey,
-        maker_keypair: Keypair,
-        mint_a: Pubkey,
-        mint_b: Pubkey,
-        vault: Pubkey,
-        expected_offer_pda: (Pubkey, u8),
-        offer_utxo: UtxoMeta,
-        id: u64,
-        program_pubkey: Pubkey,
-        client: ArchRpcClient,
-    ) {
-        let test_config = Config::localnet();
-        let bitcoin_network = test_config.network;
+    /// Makes the fixture offer of 100 A for 100 B, minting the offered token A to the maker
+    pub fn make_offer(fixture: &OfferFixture) {
+        fund_maker(fixture, fixture.mint_a, 100);
 
-        let maker_ata_a = create_ata(
-            maker_pubkey,
-            maker_pubkey,
-            maker_keypair,
-            mint_a,
-            client.clone(),
+        let make_offer = MakeOffer {
+            offer_bump_seed: fixture.offer_pda.1,
+            offer_utxo: fixture.offer_utxo,
+            id: fixture.id,
+            token_a_offered_amount: 100,
+            token_b_wanted_amount: 100,
+            expiry: None,
+            btc_script_pubkey: None,
+            allowed_taker: None,
+        };
+
+        let status = send_instruction(
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &fixture.maker_pubkey,
+                &fixture.mint_a,
+                &fixture.mint_b,
+                make_offer,
+            ),
+            fixture.maker_pubkey,
+            vec![fixture.maker_keypair],
+            fixture.client.clone(),
         );
+        assert!(status == Status::Processed);
+    }
 
+    /// Takes the whole fixture offer for `taker`, minting it the 100 B to pay first
+    pub fn take_offer(fixture: &OfferFixture, taker: &OfferTaker) {
         mint_to(
             100,
-            mint_a,
-            maker_ata_a,
-            maker_pubkey,
-            maker_keypair,
-            client.clone(),
+            fixture.mint_b,
+            taker.ata_b,
+            fixture.maker_pubkey,
+            fixture.maker_keypair,
+            fixture.client.clone(),
         );
 
-        let make_offer = MakeOffer {
-            offer_bump_seed: expected_offer_pda.1,
-            offer_utxo,
-            id,
-            token_a_offered_amount: 100,
-            token_b_wanted_amount: 100,
+        let offer = read_offer(fixture.offer_pda.0, &fixture.client);
+
+        let status = send_instruction(
+            take_offer_ix(&fixture.program_pubkey, &offer, &taker.pubkey, 100),
+            taker.pubkey,
+            vec![taker.keypair],
+            fixture.client.clone(),
+        );
+
+        assert!(status == Status::Processed);
+    }
+
+    /// Funds and signs a transaction with `outputs` from the node wallet, sends it and mines it,
+    /// returning the first output as the payment
+    fn send_btc_payment(rpc: &Client, outputs: Vec<TxOut>) -> UtxoMeta {
+        let payment = broadcast_btc_payment(rpc, outputs);
+
+        mine_block();
+        thread::sleep(Duration::from_secs(10));
+
+        payment
+    }
+
+    /// Funds and signs a transaction with `outputs` from the node wallet and sends it without
+    /// mining it, returning the first output as the payment
+    fn broadcast_btc_payment(rpc: &Client, outputs: Vec<TxOut>) -> UtxoMeta {
+        let payment_script_pubkey = outputs[0].script_pubkey.clone();
+        let unfunded_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: outputs,
         };
 
-        let serialized_maker_offer_input =
-            borsh::to_vec(&EscrowInstruction::MakeOffer(make_offer)).unwrap();
+        let funded_tx = rpc.fund_raw_transaction(&unfunded_tx, None, None).unwrap();
+        let signed_tx = rpc
+            .sign_raw_transaction_with_wallet(&funded_tx.hex, None, None)
+            .unwrap()
+            .transaction()
+            .unwrap();
+        let txid = rpc.send_raw_transaction(&signed_tx).unwrap();
 
-        let make_offer_tx = build_and_sign_transaction(
-            ArchMessage::new(
-                &[arch_program::instruction::Instruction {
-                    program_id: program_pubkey,
-                    accounts: vec![
-                        AccountMeta::new(expected_offer_pda.0, false),
-                        AccountMeta::new(mint_a, false),
-                        AccountMeta::new(mint_b, false),
-                        AccountMeta::new(maker_ata_a, false),
-                        AccountMeta::new(vault, false),
-                        AccountMeta::new(maker_pubkey, true),
-                        AccountMeta::new_readonly(apl_token::id(), false),
-                        AccountMeta::new_readonly(Pubkey::system_program(), false),
-                        AccountMeta::new_readonly(apl_associated_token_account::id(), false),
-                    ],
-                    data: serialized_maker_offer_input,
-                }],
-                Some(maker_pubkey),
-                client.get_best_finalized_block_hash().unwrap(),
-            ),
-            vec![maker_keypair],
-            bitcoin_network,
+        // the node wallet adds its change output at a random position
+        let vout = signed_tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == payment_script_pubkey)
+            .unwrap();
+
+        UtxoMeta::from(
+            hex::decode(txid.to_string()).unwrap().try_into().unwrap(),
+            vout as u32,
         )
-        .expect("Failed to build and sign transaction");
+    }
 
-        let txid = client.send_transaction(make_offer_tx).unwrap();
-        let processed_tx = client.wait_for_processed_transaction(&txid).unwrap();
-        assert!(processed_tx.status == Status::Processed);
+    fn mine_block() {
+        let config = Config::localnet();
+
+        let rpc = Client::new(
+            &config.node_endpoint,
+            Auth::UserPass(config.node_username, config.node_password),
+        )
+        .expect("rpc shouldn not fail to be initiated");
+
+        let mining_address: Address<NetworkChecked> = MINING_ADDRESS
+            .parse::<Address<_>>()
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap();
+
+        rpc.generate_to_address(1, &mining_address).unwrap();
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn take_offer(
-        maker_ata_b: Pubkey,
-        taker_ata_a: Pubkey,
-        taker_ata_b: Pubkey,
-        mint_a: Pubkey,
-        mint_b: Pubkey,
+    /// Makes offer `id` of `token_a_offered_amount` of `mints.0` for `token_b_wanted_amount` of
+    /// `mints.1` expiring at `expiry`, from an existing maker token account, the offered tokens
+    /// being minted by the fixture maker who holds the mint authority
+    fn open_offer(
+        fixture: &OfferFixture,
         maker_pubkey: Pubkey,
         maker_keypair: Keypair,
-        taker_pubkey: Pubkey,
-        taker_keypair: Keypair,
-        vault: Pubkey,
-        expected_offer_pda: (Pubkey, u8),
-        program_pubkey: Pubkey,
-        client: ArchRpcClient,
+        mints: (Pubkey, Pubkey),
+        id: u64,
+        (token_a_offered_amount, token_b_wanted_amount): (u64, u64),
+        expiry: Option<OfferExpiry>,
     ) {
-        let test_config = Config::localnet();
-        let bitcoin_network = test_config.network;
+        let config = Config::localnet();
+        let client = fixture.client.clone();
 
-        mint_to(
-            100,
-            mint_b,
-            taker_ata_b,
+        let offer_pda = find_offer_address(&fixture.program_pubkey, &maker_pubkey, id);
+
+        let helper = BitcoinHelper::new(&config).expect("Failed to create BitcoinHelper");
+        let (offer_txid, offer_vout) = helper.send_utxo(offer_pda.0).unwrap();
+        let offer_utxo = UtxoMeta::from(
+            hex::decode(offer_txid.clone()).unwrap().try_into().unwrap(),
+            offer_vout,
+        );
+
+        create_ata(
             maker_pubkey,
+            offer_pda.0,
             maker_keypair,
+            mints.0,
+            client.clone(),
+        );
+
+        mint_to(
+            token_a_offered_amount,
+            mints.0,
+            find_associated_token_address(&maker_pubkey, &mints.0),
+            fixture.maker_pubkey,
+            fixture.maker_keypair,
             client.clone(),
         );
 
-        let serialized_take_offer_input = borsh::to_vec(&EscrowInstruction::TakeOffer).unwrap();
+        let status = send_instruction(
+            make_offer_ix(
+                &fixture.program_pubkey,
+                &maker_pubkey,
+                &mints.0,
+                &mints.1,
+                MakeOffer {
+                    offer_bump_seed: offer_pda.1,
+                    offer_utxo,
+                    id,
+                    token_a_offered_amount,
+                    token_b_wanted_amount,
+                    expiry,
+                    btc_script_pubkey: None,
+                    allowed_taker: None,
+                },
+            ),
+            maker_pubkey,
+            vec![maker_keypair],
+            client,
+        );
+        assert!(status == Status::Processed);
+    }
+
+    fn read_offer(offer: Pubkey, client: &ArchRpcClient) -> Offer {
+        Offer::decode(&client.read_account_info(offer).unwrap().data).unwrap()
+    }
+
+    /// Tokens held by the token account `token_account`
+    fn token_balance(client: &ArchRpcClient, token_account: Pubkey) -> u64 {
+        apl_token::state::Account::unpack(&client.read_account_info(token_account).unwrap().data)
+            .unwrap()
+            .amount
+    }
+
+    /// Lamports held by `pubkey`, zero once the account is closed
+    fn lamports(pubkey: Pubkey, client: &ArchRpcClient) -> u64 {
+        client
+            .read_account_info(pubkey)
+            .map_or(0, |account_info| account_info.lamports)
+    }
 
-        let take_offer_tx = build_and_sign_transaction(
+    /// Sends `instruction` in its own transaction paid by `payer` and returns its status
+    fn send_instruction(
+        instruction: Instruction,
+        payer: Pubkey,
+        signers: Vec<Keypair>,
+        client: ArchRpcClient,
+    ) -> Status {
+        let transaction = build_and_sign_transaction(
             ArchMessage::new(
-                &[arch_program::instruction::Instruction {
-                    program_id: program_pubkey,
-                    accounts: vec![
-                        AccountMeta::new(expected_offer_pda.0, false),
-                        AccountMeta::new(mint_a, false),
-                        AccountMeta::new(mint_b, false),
-                        AccountMeta::new(maker_ata_b, false),
-                        AccountMeta::new(taker_ata_a, false),
-                        AccountMeta::new(taker_ata_b, false),
-                        AccountMeta::new(vault, false),
-                        AccountMeta::new(maker_pubkey, false),
-                        AccountMeta::new(taker_pubkey, true),
-                        AccountMeta::new_readonly(apl_token::id(), false),
-                        AccountMeta::new_readonly(Pubkey::system_program(), false),
-                        AccountMeta::new_readonly(apl_associated_token_account::id(), false),
-                    ],
-                    data: serialized_take_offer_input,
-                }],
-                Some(taker_pubkey),
+                &[instruction],
+                Some(payer),
                 client.get_best_finalized_block_hash().unwrap(),
             ),
-            vec![taker_keypair],
-            bitcoin_network,
+            signers,
+            Config::localnet().network,
         )
         .expect("Failed to build and sign transaction");
 
-        let txid = client.send_transaction(take_offer_tx).unwrap();
+        let txid = client.send_transaction(transaction).unwrap();
         let processed_tx = client.wait_for_processed_transaction(&txid).unwrap();
 
-        assert!(processed_tx.status == Status::Processed);
+        processed_tx.status
+    }
+
+    /// Asserts that a transaction failed with the given escrow error, accepting both the
+    /// `custom program error: 0x258` and the `Custom(600)` forms of the failure message
+    fn assert_escrow_error(status: &Status, error: EscrowError) {
+        let code = error as u32;
+
+        let Status::Failed(message) = status else {
+            panic!("expected {:?}, transaction status is {:?}", error, status);
+        };
+
+        assert!(
+            message.contains(&format!("Custom({})", code))
+                || message
+                    .to_ascii_lowercase()
+                    .contains(&format!("custom program error: {:#x}", code)),
+            "expected {:?}, transaction failed with {}",
+            error,
+            message
+        );
     }
 
     pub fn mint_to(
@@ -415,6 +1825,8 @@ mod tests {
         let test_config = Config::localnet();
         let bitcoin_network = test_config.network;
 
+        let balance_before = token_balance(&client, user_ata);
+
         let mint_to_tx = build_and_sign_transaction(
             ArchMessage::new(
                 &[apl_token::instruction::mint_to(
@@ -439,12 +1851,9 @@ mod tests {
 
         assert!(processed_tx.status == Status::Processed);
 
-        let user_ata_info = client.read_account_info(user_ata).unwrap();
         assert_eq!(
-            apl_token::state::Account::unpack(&user_ata_info.data)
-                .unwrap()
-                .amount,
-            mint_amount
+            token_balance(&client, user_ata),
+            balance_before + mint_amount
         );
     }
 }
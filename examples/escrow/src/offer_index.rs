@@ -0,0 +1,319 @@
+//! Discovery of open offers: scans the accounts owned by the escrow program, decodes the offers
+//! among them and answers queries by maker, mint pair, price or expiry.
+use arch_program::pubkey::Pubkey;
+use arch_sdk::{blocking::ArchRpcClient, AccountFilter, ArchError};
+use escrow_interface::state::Offer;
+
+/// Offset of `Offer::maker` in offer accounts, after the `u64` id
+const MAKER_OFFSET: usize = 8;
+/// Offset of `Offer::token_mint_a` in offer accounts
+const TOKEN_MINT_A_OFFSET: usize = MAKER_OFFSET + 32;
+/// Offset of `Offer::token_mint_b` in offer accounts
+const TOKEN_MINT_B_OFFSET: usize = TOKEN_MINT_A_OFFSET + 32;
+
+/// An offer still open on chain, along with its address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOffer {
+    pub address: Pubkey,
+    pub offer: Offer,
+}
+
+/// Price of an offer, `token_b` asked for `token_a` offered, compared as an exact fraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Price {
+    pub token_b: u64,
+    pub token_a: u64,
+}
+
+impl Price {
+    pub fn new(token_b: u64, token_a: u64) -> Self {
+        Price { token_b, token_a }
+    }
+
+    fn of(offer: &Offer) -> Self {
+        Price::new(offer.token_b_wanted_amount, offer.token_a_offered_amount)
+    }
+
+    /// Whether this price is at most `other`, cross multiplied to avoid rounding
+    fn at_most(&self, other: &Price) -> bool {
+        u128::from(self.token_b) * u128::from(other.token_a)
+            <= u128::from(other.token_b) * u128::from(self.token_a)
+    }
+}
+
+/// Conditions an offer has to meet to be returned by `OfferIndex::query`, all of them when
+/// several are set. Native bitcoin offers a taker already registered a payment for are left out
+/// unless `include_pending_payments` is set.
+#[derive(Debug, Clone, Default)]
+pub struct OfferFilter {
+    pub maker: Option<Pubkey>,
+    pub token_mint_a: Option<Pubkey>,
+    pub token_mint_b: Option<Pubkey>,
+    pub min_price: Option<Price>,
+    pub max_price: Option<Price>,
+    /// Leaves out the offers reserved to another taker
+    pub taker: Option<Pubkey>,
+    /// Leaves out the offers expired once the Bitcoin chain is at this block height and the Arch
+    /// clock at this unix timestamp
+    pub not_expired_at: Option<(u64, i64)>,
+    /// Keeps the offers with a registered bitcoin payment, which only its taker can settle
+    pub include_pending_payments: bool,
+}
+
+impl OfferFilter {
+    pub fn maker(mut self, maker: Pubkey) -> Self {
+        self.maker = Some(maker);
+        self
+    }
+
+    /// Offers of `token_mint_a` for `token_mint_b`
+    pub fn mint_pair(mut self, token_mint_a: Pubkey, token_mint_b: Pubkey) -> Self {
+        self.token_mint_a = Some(token_mint_a);
+        self.token_mint_b = Some(token_mint_b);
+        self
+    }
+
+    pub fn min_price(mut self, price: Price) -> Self {
+        self.min_price = Some(price);
+        self
+    }
+
+    pub fn max_price(mut self, price: Price) -> Self {
+        self.max_price = Some(price);
+        self
+    }
+
+    pub fn taker(mut self, taker: Pubkey) -> Self {
+        self.taker = Some(taker);
+        self
+    }
+
+    pub fn not_expired_at(mut self, bitcoin_block_height: u64, unix_timestamp: i64) -> Self {
+        self.not_expired_at = Some((bitcoin_block_height, unix_timestamp));
+        self
+    }
+
+    pub fn include_pending_payments(mut self) -> Self {
+        self.include_pending_payments = true;
+        self
+    }
+
+    pub fn matches(&self, offer: &Offer) -> bool {
+        let price = Price::of(offer);
+
+        self.maker.is_none_or(|maker| offer.maker == maker)
+            && self
+                .token_mint_a
+                .is_none_or(|token_mint_a| offer.token_mint_a == token_mint_a)
+            && self
+                .token_mint_b
+                .is_none_or(|token_mint_b| offer.token_mint_b == token_mint_b)
+            && self
+                .min_price
+                .is_none_or(|min_price| min_price.at_most(&price))
+            && self
+                .max_price
+                .is_none_or(|max_price| price.at_most(&max_price))
+            && self.taker.is_none_or(|taker| offer.allows_taker(&taker))
+            && self
+                .not_expired_at
+                .is_none_or(|(bitcoin_block_height, unix_timestamp)| {
+                    !offer.is_expired(bitcoin_block_height, unix_timestamp)
+                })
+            && (self.include_pending_payments || offer.btc_payment.is_none())
+    }
+
+    /// Narrows the account scan on the node side with the maker and mints of the filter
+    fn account_filters(&self) -> Vec<AccountFilter> {
+        [
+            (MAKER_OFFSET, self.maker),
+            (TOKEN_MINT_A_OFFSET, self.token_mint_a),
+            (TOKEN_MINT_B_OFFSET, self.token_mint_b),
+        ]
+        .into_iter()
+        .filter_map(|(offset, pubkey)| {
+            pubkey.map(|pubkey| AccountFilter::DataContent {
+                offset,
+                bytes: pubkey.as_ref().to_vec(),
+            })
+        })
+        .collect()
+    }
+}
+
+/// Snapshot of the open offers of an escrow program, taken by `OfferIndex::scan`
+#[derive(Debug, Clone, Default)]
+pub struct OfferIndex {
+    offers: Vec<OpenOffer>,
+}
+
+impl OfferIndex {
+    /// Reads the accounts owned by `program_id` matching the maker and mints of `filter`, keeping
+    /// the ones holding an offer. Closed offers are handed back to the system program and don't
+    /// show up.
+    pub fn scan(
+        client: &ArchRpcClient,
+        program_id: &Pubkey,
+        filter: &OfferFilter,
+    ) -> Result<Self, ArchError> {
+        let account_filters = filter.account_filters();
+        let accounts = client.get_program_accounts(
+            program_id,
+            (!account_filters.is_empty()).then_some(account_filters),
+        )?;
+
+        Ok(OfferIndex::from_accounts(accounts.into_iter().map(
+            |program_account| (program_account.pubkey, program_account.account.data),
+        )))
+    }
+
+    /// Builds the index from `(address, data)` account pairs, skipping accounts that are not
+    /// offers
+    pub fn from_accounts(accounts: impl IntoIterator<Item = (Pubkey, Vec<u8>)>) -> Self {
+        let offers = accounts
+            .into_iter()
+            .filter_map(|(address, data)| {
+                Offer::decode(&data)
+                    .ok()
+                    .map(|offer| OpenOffer { address, offer })
+            })
+            .collect();
+
+        OfferIndex { offers }
+    }
+
+    pub fn offers(&self) -> &[OpenOffer] {
+        &self.offers
+    }
+
+    /// Offers matching `filter`, in the order the node returned them
+    pub fn query(&self, filter: &OfferFilter) -> Vec<&OpenOffer> {
+        self.offers
+            .iter()
+            .filter(|open_offer| filter.matches(&open_offer.offer))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arch_program::utxo::UtxoMeta;
+    use escrow_interface::state::{OfferExpiry, PendingBtcPayment};
+
+    fn offer(id: u64, maker: u8, mints: (u8, u8), token_a: u64, token_b: u64) -> Offer {
+        Offer {
+            id,
+            maker: Pubkey::from_slice(&[maker; 32]),
+            token_mint_a: Pubkey::from_slice(&[mints.0; 32]),
+            token_mint_b: Pubkey::from_slice(&[mints.1; 32]),
+            token_b_wanted_amount: token_b,
+            btc_script_pubkey: None,
+            token_a_offered_amount: token_a,
+            token_a_remaining_amount: token_a,
+            expiry: None,
+            fee: None,
+            allowed_taker: None,
+            btc_payment: None,
+            bump: 255,
+        }
+    }
+
+    fn index(offers: &[Offer]) -> OfferIndex {
+        let mut accounts: Vec<(Pubkey, Vec<u8>)> = offers
+            .iter()
+            .map(|offer| {
+                (
+                    Pubkey::from_slice(&[100 + offer.id as u8; 32]),
+                    borsh::to_vec(offer).unwrap(),
+                )
+            })
+            .collect();
+        // closed offer and foreign account data
+        accounts.push((Pubkey::from_slice(&[200; 32]), vec![]));
+        accounts.push((Pubkey::from_slice(&[201; 32]), vec![1, 2, 3]));
+
+        OfferIndex::from_accounts(accounts)
+    }
+
+    fn ids(offers: Vec<&OpenOffer>) -> Vec<u64> {
+        offers
+            .iter()
+            .map(|open_offer| open_offer.offer.id)
+            .collect()
+    }
+
+    #[test]
+    fn query_offers() {
+        let mut index = index(&[
+            offer(1, 1, (10, 11), 100, 30),
+            offer(2, 1, (10, 11), 100, 200),
+            offer(3, 1, (11, 10), 50, 10),
+            offer(4, 2, (10, 11), 10, 5),
+        ]);
+
+        assert_eq!(index.offers().len(), 4);
+        assert_eq!(index.offers()[0].address, Pubkey::from_slice(&[101; 32]));
+
+        let maker = Pubkey::from_slice(&[1; 32]);
+        let (mint_a, mint_b) = (Pubkey::from_slice(&[10; 32]), Pubkey::from_slice(&[11; 32]));
+
+        assert_eq!(ids(index.query(&OfferFilter::default())), [1, 2, 3, 4]);
+        assert_eq!(
+            ids(index.query(&OfferFilter::default().maker(maker))),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            ids(index.query(&OfferFilter::default().mint_pair(mint_a, mint_b))),
+            [1, 2, 4]
+        );
+        assert_eq!(
+            ids(index.query(
+                &OfferFilter::default()
+                    .mint_pair(mint_a, mint_b)
+                    .max_price(Price::new(1, 2))
+            )),
+            [1, 4]
+        );
+        assert_eq!(
+            ids(index.query(
+                &OfferFilter::default()
+                    .maker(maker)
+                    .mint_pair(mint_a, mint_b)
+                    .min_price(Price::new(1, 2))
+            )),
+            [2]
+        );
+
+        let taker = Pubkey::from_slice(&[3; 32]);
+        index.offers[1].offer.allowed_taker = Some(taker);
+        index.offers[2].offer.allowed_taker = Some(Pubkey::from_slice(&[4; 32]));
+        assert_eq!(
+            ids(index.query(&OfferFilter::default().taker(taker))),
+            [1, 2, 4]
+        );
+
+        index.offers[0].offer.expiry = Some(OfferExpiry::BitcoinBlockHeight(800));
+        index.offers[1].offer.expiry = Some(OfferExpiry::UnixTimestamp(1_700_000_000));
+        assert_eq!(ids(index.query(&OfferFilter::default())), [1, 2, 3, 4]);
+        assert_eq!(
+            ids(index.query(&OfferFilter::default().not_expired_at(799, 1_700_000_000))),
+            [1, 3, 4]
+        );
+        assert_eq!(
+            ids(index.query(&OfferFilter::default().not_expired_at(800, 1_699_999_999))),
+            [2, 3, 4]
+        );
+
+        index.offers[3].offer.btc_payment = Some(PendingBtcPayment {
+            payment: UtxoMeta::from([7; 32], 0),
+            taker,
+            registered_at: 790,
+        });
+        assert_eq!(ids(index.query(&OfferFilter::default())), [1, 2, 3]);
+        assert_eq!(
+            ids(index.query(&OfferFilter::default().include_pending_payments())),
+            [1, 2, 3, 4]
+        );
+    }
+}